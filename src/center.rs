@@ -0,0 +1,314 @@
+//! Finding the center of a set of coordinates in latency space
+//!
+//! The weighted centroid is the point minimizing the (weighted) sum of squared
+//! distances to each coordinate and is cheap to compute. The geometric median
+//! instead minimizes the (weighted) sum of distances, i.e. the mean estimated
+//! RTT from the group, and is not dragged around by a few far away outliers.
+//!
+//! Both return a [`Coord`] that can be passed directly to
+//! [`Coord::distance_to`]. The returned coordinate's height and error estimate
+//! are the weighted means of the inputs' heights and error estimates, and its
+//! offset is always `0.0` as offsets belong to a particular node's adjustment
+//! window.
+//!
+//! ```rust
+//! use violin::{center, heapless::VecD, Coord};
+//!
+//! let clients = [
+//!     Coord::from(VecD::from([0.0, 0.0])),
+//!     Coord::from(VecD::from([1.0, 0.0])),
+//!     Coord::from(VecD::from([0.0, 1.0])),
+//!     Coord::from(VecD::from([100.0, 100.0])),
+//! ];
+//!
+//! let mean = center::centroid(&clients, None).unwrap();
+//! let median = center::geometric_median(&clients, None, 1.0e-9).unwrap();
+//!
+//! // The outlier pulls the centroid far more than the median
+//! assert!(mean.distance_to(&clients[0]) > 30.0);
+//! assert!(median.distance_to(&clients[0]) < 1.0);
+//! ```
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    matrix::check_dimensions,
+    Coord, Vector, OVERLAP_THRESHOLD,
+};
+
+/// The maximum number of Weiszfeld iterations performed by
+/// [`geometric_median`] before returning the current estimate
+pub const MAX_ITERATIONS: usize = 1000;
+
+/// Returns the weighted centroid of `coords`
+///
+/// If `weights` is `None` every coordinate has a weight of `1.0`.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - `coords` is empty, or `weights` is not the same length as `coords`
+/// - The coordinates have different dimensions
+/// - A weight is negative, NaN or Infinite, or all weights are `0.0`
+pub fn centroid<T>(coords: &[Coord<T>], weights: Option<&[f64]>) -> Result<Coord<T>>
+where
+    T: Vector + Clone,
+{
+    let total = total_weight(coords, weights)?;
+    check_dimensions(coords)?;
+
+    // An empty vector is the origin, so size the sum by any other
+    let first = coords.iter().find(|c| !c.vec.as_ref().is_empty());
+    let mut vec = first.unwrap_or(&coords[0]).vec.clone() * 0.0;
+    for (i, c) in coords.iter().enumerate() {
+        let w = weight(weights, i) / total;
+        for (v, x) in vec.as_mut().iter_mut().zip(c.vec.as_ref()) {
+            *v += w * x;
+        }
+    }
+
+    Ok(combine(coords, weights, total, vec))
+}
+
+/// Returns the weighted geometric median of `coords` using Weiszfeld's
+/// algorithm (with the Vardi-Zhang modification for when an iterate lands on
+/// one of the coordinates)
+///
+/// Iteration starts from the weighted centroid and stops once an iteration
+/// moves less than `threshold`, or after [`MAX_ITERATIONS`].
+///
+/// If `weights` is `None` every coordinate has a weight of `1.0`.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - `coords` is empty, or `weights` is not the same length as `coords`
+/// - The coordinates have different dimensions
+/// - A weight is negative, NaN or Infinite, or all weights are `0.0`
+pub fn geometric_median<T>(
+    coords: &[Coord<T>],
+    weights: Option<&[f64]>,
+    threshold: f64,
+) -> Result<Coord<T>>
+where
+    T: Vector + Clone,
+{
+    let total = total_weight(coords, weights)?;
+    check_dimensions(coords)?;
+    let mut cur = centroid(coords, weights)?.vec;
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut num = cur.clone() * 0.0;
+        let mut denom = 0.0;
+        // Weight of the coordinates the current estimate sits on top of
+        let mut eta = 0.0;
+        for (i, c) in coords.iter().enumerate() {
+            let w = weight(weights, i);
+            let d = c.vec.distance(&cur);
+            if d < OVERLAP_THRESHOLD {
+                eta += w;
+                continue;
+            }
+            let f = w / d;
+            for (n, x) in num.as_mut().iter_mut().zip(c.vec.as_ref()) {
+                *n += f * x;
+            }
+            denom += f;
+        }
+
+        if denom == 0.0 {
+            // Every weighted coordinate overlaps the current estimate
+            break;
+        }
+
+        let t = num / denom;
+        let next = if eta == 0.0 {
+            t
+        } else {
+            let r = t.difference(&cur).magnitude() * denom;
+            if r <= eta {
+                // The overlapped coordinate is itself the median
+                break;
+            }
+            let gamma = eta / r;
+            t * (1.0 - gamma) + cur.clone() * gamma
+        };
+
+        let moved = next.distance(&cur);
+        cur = next;
+        if moved <= threshold {
+            break;
+        }
+    }
+
+    Ok(combine(coords, weights, total, cur))
+}
+
+#[inline(always)]
+pub(crate) fn weight(weights: Option<&[f64]>, i: usize) -> f64 { weights.map_or(1.0, |w| w[i]) }
+
+pub(crate) fn total_weight<T>(coords: &[Coord<T>], weights: Option<&[f64]>) -> Result<f64> {
+    if coords.is_empty() || weights.map_or(false, |w| w.len() != coords.len()) {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+
+    let weights = match weights {
+        Some(w) => w,
+        None => return Ok(coords.len() as f64),
+    };
+    if weights.iter().any(|w| !w.is_finite() || *w < 0.0) {
+        return Err(Error {
+            kind: ErrorKind::InvalidWeight,
+        });
+    }
+    let total = weights.iter().sum::<f64>();
+    if total <= 0.0 {
+        return Err(Error {
+            kind: ErrorKind::InvalidWeight,
+        });
+    }
+    Ok(total)
+}
+
+/// Builds the resulting coordinate from `vec` and the weighted means of the
+/// heights and error estimates of `coords`
+fn combine<T>(coords: &[Coord<T>], weights: Option<&[f64]>, total: f64, vec: T) -> Coord<T> {
+    let mut height = 0.0;
+    let mut error_estimate = 0.0;
+    for (i, c) in coords.iter().enumerate() {
+        let w = weight(weights, i) / total;
+        height += w * c.height;
+        error_estimate += w * c.error_estimate;
+    }
+
+    Coord {
+        vec,
+        error_estimate: f64::max(error_estimate, OVERLAP_THRESHOLD),
+        height,
+        offset: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{close, heapless::VecD};
+
+    #[test]
+    fn centroid_unweighted() {
+        let coords = [
+            Coord::from(VecD::from([1.0, 1.0])),
+            Coord::from(VecD::from([-1.0, 1.0])),
+            Coord::from(VecD::from([-1.0, -1.0])),
+            Coord::from(VecD::from([1.0, -1.0])),
+        ];
+        let c = centroid(&coords, None).unwrap();
+        assert_eq!(c.raw_coord().as_ref(), &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn centroid_weighted() {
+        let mut coords = [
+            Coord::from(VecD::from([0.0, 0.0])),
+            Coord::from(VecD::from([4.0, 0.0])),
+        ];
+        coords[0].set_height(1.0);
+        coords[1].set_height(2.0);
+        coords[0].set_error_estimate(0.2);
+        coords[1].set_error_estimate(0.6);
+        let c = centroid(&coords, Some(&[3.0, 1.0])).unwrap();
+        assert_eq!(c.raw_coord().as_ref(), &[1.0, 0.0]);
+        assert_eq!(c.height(), 1.25);
+        assert!(close(c.error_estimate(), 0.3));
+        assert_eq!(c.offset(), 0.0);
+    }
+
+    #[test]
+    fn median_collinear() {
+        let coords = [
+            Coord::from(VecD::from([0.0, 0.0])),
+            Coord::from(VecD::from([1.0, 0.0])),
+            Coord::from(VecD::from([10.0, 0.0])),
+        ];
+        let m = geometric_median(&coords, None, 1.0e-12).unwrap();
+        assert!(close(m.raw_coord().as_ref()[0], 1.0));
+        assert!(close(m.raw_coord().as_ref()[1], 0.0));
+    }
+
+    #[test]
+    fn median_heavy_point() {
+        // A point holding more than half the weight is always the median
+        let coords = [
+            Coord::from(VecD::from([0.0, 0.0])),
+            Coord::from(VecD::from([5.0, 5.0])),
+            Coord::from(VecD::from([-3.0, 8.0])),
+        ];
+        let m = geometric_median(&coords, Some(&[10.0, 1.0, 1.0]), 1.0e-12).unwrap();
+        assert!(m.raw_coord().distance(coords[0].raw_coord()) < 1.0e-6);
+    }
+
+    #[test]
+    fn median_minimizes_total_distance() {
+        let coords = [
+            Coord::from(VecD::from([0.0, 0.0, 1.0])),
+            Coord::from(VecD::from([3.0, 1.0, 0.0])),
+            Coord::from(VecD::from([1.0, 4.0, 2.0])),
+            Coord::from(VecD::from([-2.0, 2.0, 5.0])),
+            Coord::from(VecD::from([7.0, -1.0, 3.0])),
+        ];
+        let total = |c: &Coord<VecD<3>>| coords.iter().map(|o| c.distance_to(o)).sum::<f64>();
+        let m = geometric_median(&coords, None, 1.0e-12).unwrap();
+        let mean = centroid(&coords, None).unwrap();
+        assert!(total(&m) <= total(&mean));
+        for o in &coords {
+            assert!(total(&m) <= total(o));
+        }
+    }
+
+    #[test]
+    fn invalid_input() {
+        let empty: [Coord<VecD<2>>; 0] = [];
+        assert!(centroid(&empty, None).unwrap_err().is_invalid_length());
+
+        let coords = [
+            Coord::from(VecD::from([0.0, 0.0])),
+            Coord::from(VecD::from([1.0, 0.0])),
+        ];
+        assert!(centroid(&coords, Some(&[1.0]))
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(centroid(&coords, Some(&[1.0, -1.0]))
+            .unwrap_err()
+            .is_invalid_weight());
+        assert!(geometric_median(&coords, Some(&[0.0, 0.0]), 0.0)
+            .unwrap_err()
+            .is_invalid_weight());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn dynamic_dimensions() {
+        use crate::dynamic::VecD;
+
+        // an empty vector is the origin of every dimension
+        let c = vec![
+            Coord::from(VecD::from(vec![])),
+            Coord::from(VecD::from([3.0, 0.0])),
+            Coord::from(VecD::from([0.0, 3.0])),
+        ];
+        let mean = centroid(&c, None).unwrap();
+        assert!(close(mean.vec.as_ref()[0], 1.0));
+        assert!(close(mean.vec.as_ref()[1], 1.0));
+        geometric_median(&c, None, 1.0e-9).unwrap();
+
+        let mut c = c;
+        c.push(Coord::from(VecD::from([1.0, 2.0, 3.0])));
+        assert!(centroid(&c, None).unwrap_err().is_dimension_mismatch());
+        assert!(geometric_median(&c, None, 1.0e-9)
+            .unwrap_err()
+            .is_dimension_mismatch());
+    }
+}
@@ -0,0 +1,558 @@
+//! Splitting a set of coordinates into latency zones
+//!
+//! Both [`kmeans`] and [`kmedoids`] measure closeness with
+//! [`Coord::distance_to`], so heights and offsets are taken into account just
+//! as they would be for a real RTT estimate. Initial centers are chosen with
+//! k-means++ seeding from the caller supplied RNG, so results are reproducible
+//! given a seeded RNG.
+//!
+//! To help pick `k`, cluster with several values and compare their
+//! [`silhouette`] scores; higher is better.
+//!
+//! ```rust
+//! use rand::{rngs::StdRng, SeedableRng};
+//! use violin::{cluster, heapless::VecD, Coord};
+//!
+//! let coords = [
+//!     Coord::from(VecD::from([0.0, 0.0])),
+//!     Coord::from(VecD::from([0.1, 0.0])),
+//!     Coord::from(VecD::from([10.0, 10.0])),
+//!     Coord::from(VecD::from([10.1, 10.0])),
+//! ];
+//!
+//! let mut rng = StdRng::seed_from_u64(42);
+//! let zones = cluster::kmedoids(&coords, 2, 100, &mut rng).unwrap();
+//! assert_eq!(zones.assignments[0], zones.assignments[1]);
+//! assert_ne!(zones.assignments[0], zones.assignments[2]);
+//! assert!(cluster::silhouette(&coords, &zones.assignments).unwrap() > 0.9);
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use rand::Rng;
+
+use crate::{
+    center,
+    error::{Error, ErrorKind, Result},
+    matrix, Coord, Vector,
+};
+
+/// The result of clustering a set of coordinates
+#[derive(Debug, Clone)]
+pub struct Clustering<T> {
+    /// The cluster index of each input coordinate, in input order
+    pub assignments: Vec<usize>,
+    /// The center of each cluster. For [`kmeans`] this is the cluster
+    /// centroid, for [`kmedoids`] it is the medoid's coordinate.
+    pub centers: Vec<Coord<T>>,
+    /// The index (into the input coordinates) of each cluster's medoid, the
+    /// member with the lowest total estimated RTT to all other members
+    pub medoids: Vec<usize>,
+    /// Estimated RTT statistics for each cluster
+    pub stats: Vec<ClusterStats>,
+}
+
+/// Estimated RTT statistics within a single cluster
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct ClusterStats {
+    /// The number of members in the cluster
+    pub size: usize,
+    /// Mean estimated RTT between each pair of distinct members
+    pub mean_rtt: f64,
+    /// Largest estimated RTT between any pair of distinct members
+    pub max_rtt: f64,
+    /// Mean estimated RTT from each member to the cluster center
+    pub mean_rtt_to_center: f64,
+}
+
+/// Clusters `coords` into `k` zones using k-means (Lloyd's algorithm)
+///
+/// Coordinates are assigned to the center with the lowest
+/// [`Coord::distance_to`], and centers are moved to the
+/// [`center::centroid`] of their members. Stops when no assignment changes or
+/// after `max_iter` rounds.
+///
+/// # Errors
+///
+/// Returns an error if `k == 0`, `k > coords.len()`, or the coordinates have
+/// different dimensions
+pub fn kmeans<T, R>(
+    coords: &[Coord<T>],
+    k: usize,
+    max_iter: usize,
+    rng: &mut R,
+) -> Result<Clustering<T>>
+where
+    T: Vector + Clone,
+    R: Rng + ?Sized,
+{
+    check_k(coords.len(), k)?;
+    matrix::check_dimensions(coords)?;
+
+    let mut centers: Vec<_> = seed(coords.len(), k, rng, |i, j| {
+        coords[i].distance_to(&coords[j])
+    })
+    .into_iter()
+    .map(|i| coords[i].clone())
+    .collect();
+    let mut assignments = vec![usize::MAX; coords.len()];
+    let mut mask = vec![0.0; coords.len()];
+
+    let mut iter = 0;
+    loop {
+        let dist = |i: usize, c: usize| coords[i].distance_to(&centers[c]);
+        let mut changed = assign(&mut assignments, k, dist);
+        for (c, i) in fill_empty(&mut assignments, k, dist) {
+            centers[c] = coords[i].clone();
+            changed = true;
+        }
+        iter += 1;
+        if !changed || iter >= max_iter {
+            break;
+        }
+
+        for (c, center) in centers.iter_mut().enumerate() {
+            for (m, a) in mask.iter_mut().zip(&assignments) {
+                *m = if *a == c { 1.0 } else { 0.0 };
+            }
+            *center = center::centroid(coords, Some(&mask))?;
+        }
+    }
+
+    let medoids = (0..k)
+        .map(|c| medoid(&assignments, c, |i, j| coords[i].distance_to(&coords[j])))
+        .collect();
+    let stats = stats(coords, &assignments, &centers);
+
+    Ok(Clustering {
+        assignments,
+        centers,
+        medoids,
+        stats,
+    })
+}
+
+/// Clusters `coords` into `k` zones using k-medoids
+///
+/// Every center is one of the input coordinates. Coordinates are assigned to
+/// the medoid with the lowest [`Coord::distance_to`], and each medoid is
+/// replaced by the member with the lowest total estimated RTT to the rest of
+/// its cluster. Stops when no assignment changes or after `max_iter` rounds.
+///
+/// This precomputes the full `N×N` estimated RTT matrix, so requires
+/// `O(N²)` memory.
+///
+/// # Errors
+///
+/// Returns an error if `k == 0`, `k > coords.len()`, or the coordinates have
+/// different dimensions
+pub fn kmedoids<T, R>(
+    coords: &[Coord<T>],
+    k: usize,
+    max_iter: usize,
+    rng: &mut R,
+) -> Result<Clustering<T>>
+where
+    T: Vector + Clone,
+    R: Rng + ?Sized,
+{
+    let n = coords.len();
+    check_k(n, k)?;
+
+    let mut dist = vec![0.0; n * n];
+    matrix::distance_matrix(coords, &mut dist)?;
+    // A coordinate is always closest to itself, regardless of its height
+    let d = |i: usize, j: usize| if i == j { 0.0 } else { dist[i * n + j] };
+
+    let mut medoids = seed(n, k, rng, d);
+    let mut assignments = vec![usize::MAX; n];
+
+    let mut iter = 0;
+    loop {
+        let mut changed = assign(&mut assignments, k, |i, c| d(i, medoids[c]));
+        for (c, i) in fill_empty(&mut assignments, k, |i, c| d(i, medoids[c])) {
+            medoids[c] = i;
+            changed = true;
+        }
+        iter += 1;
+        if !changed || iter >= max_iter {
+            break;
+        }
+
+        for (c, m) in medoids.iter_mut().enumerate() {
+            *m = medoid(&assignments, c, d);
+        }
+    }
+
+    let centers: Vec<_> = medoids.iter().map(|m| coords[*m].clone()).collect();
+    let stats = stats(coords, &assignments, &centers);
+
+    Ok(Clustering {
+        assignments,
+        centers,
+        medoids,
+        stats,
+    })
+}
+
+/// Returns the mean silhouette score of a clustering, in the range
+/// `[-1.0, 1.0]`
+///
+/// A score near `1.0` means coordinates are much closer to their own cluster
+/// than to the nearest other cluster, near `0.0` means clusters overlap.
+/// Members of single member clusters score `0.0`, as does a clustering with
+/// only one cluster.
+///
+/// # Errors
+///
+/// Returns an error if `assignments` is not the same length as `coords`, or
+/// `coords` is empty
+pub fn silhouette<T: Vector>(coords: &[Coord<T>], assignments: &[usize]) -> Result<f64> {
+    if coords.is_empty() || coords.len() != assignments.len() {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+
+    let k = assignments.iter().max().map_or(0, |m| m + 1);
+    let mut sizes = vec![0usize; k];
+    for a in assignments {
+        sizes[*a] += 1;
+    }
+    if sizes.iter().filter(|s| **s > 0).count() < 2 {
+        return Ok(0.0);
+    }
+
+    let mut sums = vec![0.0; k];
+    let mut total = 0.0;
+    for (i, ci) in coords.iter().enumerate() {
+        let own = assignments[i];
+        if sizes[own] < 2 {
+            continue;
+        }
+
+        sums.iter_mut().for_each(|s| *s = 0.0);
+        for (j, cj) in coords.iter().enumerate() {
+            if i != j {
+                sums[assignments[j]] += ci.distance_to(cj);
+            }
+        }
+
+        let a = sums[own] / (sizes[own] - 1) as f64;
+        let b = sums
+            .iter()
+            .zip(&sizes)
+            .enumerate()
+            .filter(|(c, (_, size))| *c != own && **size > 0)
+            .map(|(_, (sum, size))| sum / *size as f64)
+            .fold(f64::INFINITY, f64::min);
+        let max = f64::max(a, b);
+        if max > 0.0 {
+            total += (b - a) / max;
+        }
+    }
+
+    Ok(total / coords.len() as f64)
+}
+
+fn check_k(n: usize, k: usize) -> Result<()> {
+    if k == 0 || k > n {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+    Ok(())
+}
+
+/// Picks `k` distinct initial centers using k-means++ seeding
+fn seed<R, F>(n: usize, k: usize, rng: &mut R, dist: F) -> Vec<usize>
+where
+    R: Rng + ?Sized,
+    F: Fn(usize, usize) -> f64,
+{
+    let mut chosen = Vec::with_capacity(k);
+    chosen.push(rng.gen_range(0..n));
+
+    let dist2 = |i, j| {
+        let d = dist(i, j);
+        // `f64::powi` is not available in `core`
+        d * d
+    };
+    // Squared distance from each coordinate to its nearest chosen center, or
+    // zero for those already chosen
+    let mut weights: Vec<f64> = (0..n).map(|i| dist2(i, chosen[0])).collect();
+    weights[chosen[0]] = 0.0;
+
+    while chosen.len() < k {
+        let total: f64 = weights.iter().sum();
+        let next = if total > 0.0 {
+            let mut target = rng.gen::<f64>() * total;
+            let mut pick = None;
+            for (i, w) in weights.iter().enumerate() {
+                if *w > 0.0 {
+                    pick = Some(i);
+                    if target < *w {
+                        break;
+                    }
+                    target -= w;
+                }
+            }
+            pick.unwrap()
+        } else {
+            // Everything left overlaps a chosen center; pick any unchosen one
+            let left: Vec<_> = (0..n).filter(|i| !chosen.contains(i)).collect();
+            left[rng.gen_range(0..left.len())]
+        };
+
+        chosen.push(next);
+        for (i, w) in weights.iter_mut().enumerate() {
+            *w = if chosen.contains(&i) {
+                0.0
+            } else {
+                f64::min(*w, dist2(i, next))
+            };
+        }
+    }
+
+    chosen
+}
+
+/// Assigns each coordinate to its nearest of `k` centers, returning `true` if
+/// any assignment changed
+fn assign<F>(assignments: &mut [usize], k: usize, dist: F) -> bool
+where
+    F: Fn(usize, usize) -> f64,
+{
+    let mut changed = false;
+    for (i, a) in assignments.iter_mut().enumerate() {
+        let mut best = 0;
+        let mut best_dist = dist(i, 0);
+        for c in 1..k {
+            let d = dist(i, c);
+            if d < best_dist {
+                best = c;
+                best_dist = d;
+            }
+        }
+        if *a != best {
+            *a = best;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Moves the coordinate farthest from its center into each empty cluster,
+/// returning the `(cluster, coordinate)` pairs that were moved
+fn fill_empty<F>(assignments: &mut [usize], k: usize, dist: F) -> Vec<(usize, usize)>
+where
+    F: Fn(usize, usize) -> f64,
+{
+    let mut sizes = vec![0usize; k];
+    for a in assignments.iter() {
+        sizes[*a] += 1;
+    }
+
+    let mut moved = Vec::new();
+    for c in 0..k {
+        if sizes[c] > 0 {
+            continue;
+        }
+        let mut far = None;
+        let mut far_dist = f64::NEG_INFINITY;
+        for (i, a) in assignments.iter().enumerate() {
+            if sizes[*a] > 1 && dist(i, *a) > far_dist {
+                far = Some(i);
+                far_dist = dist(i, *a);
+            }
+        }
+        // Since `k <= N` there is always a cluster with a member to spare
+        if let Some(i) = far {
+            sizes[assignments[i]] -= 1;
+            sizes[c] = 1;
+            assignments[i] = c;
+            moved.push((c, i));
+        }
+    }
+    moved
+}
+
+/// Returns the member of the (non-empty) cluster `c` with the lowest total
+/// distance to the other members
+fn medoid<F>(assignments: &[usize], c: usize, dist: F) -> usize
+where
+    F: Fn(usize, usize) -> f64,
+{
+    let members: Vec<_> = (0..assignments.len())
+        .filter(|i| assignments[*i] == c)
+        .collect();
+    let mut best = members[0];
+    let mut best_sum = f64::INFINITY;
+    for &m in &members {
+        let sum: f64 = members
+            .iter()
+            .filter(|j| **j != m)
+            .map(|j| dist(m, *j))
+            .sum();
+        if sum < best_sum {
+            best = m;
+            best_sum = sum;
+        }
+    }
+    best
+}
+
+fn stats<T: Vector>(
+    coords: &[Coord<T>],
+    assignments: &[usize],
+    centers: &[Coord<T>],
+) -> Vec<ClusterStats> {
+    let mut stats = vec![ClusterStats::default(); centers.len()];
+    let mut pairs = vec![0usize; centers.len()];
+
+    for (i, ci) in coords.iter().enumerate() {
+        let c = assignments[i];
+        let s = &mut stats[c];
+        s.size += 1;
+        s.mean_rtt_to_center += ci.distance_to(&centers[c]);
+        for (cj, _) in coords[i + 1..]
+            .iter()
+            .zip(&assignments[i + 1..])
+            .filter(|(_, a)| **a == c)
+        {
+            let d = ci.distance_to(cj);
+            s.mean_rtt += d;
+            s.max_rtt = f64::max(s.max_rtt, d);
+            pairs[c] += 1;
+        }
+    }
+
+    for (s, p) in stats.iter_mut().zip(pairs) {
+        if s.size > 0 {
+            s.mean_rtt_to_center /= s.size as f64;
+        }
+        if p > 0 {
+            s.mean_rtt /= p as f64;
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::heapless::VecD;
+
+    /// Three well separated zones of five coordinates each
+    fn zones() -> Vec<Coord<VecD<2>>> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut coords = Vec::new();
+        for center in [[0.0, 0.0], [20.0, 0.0], [0.0, 20.0]] {
+            for _ in 0..5 {
+                let mut c = Coord::from(VecD::from([
+                    center[0] + rng.gen_range(-1.0..1.0),
+                    center[1] + rng.gen_range(-1.0..1.0),
+                ]));
+                c.set_height(rng.gen_range(0.0..0.5));
+                coords.push(c);
+            }
+        }
+        coords
+    }
+
+    fn assert_zones<T>(c: &Clustering<T>) {
+        for zone in c.assignments.chunks(5) {
+            assert!(zone.iter().all(|a| *a == zone[0]));
+        }
+        assert_ne!(c.assignments[0], c.assignments[5]);
+        assert_ne!(c.assignments[0], c.assignments[10]);
+        assert_ne!(c.assignments[5], c.assignments[10]);
+        for s in &c.stats {
+            assert_eq!(s.size, 5);
+            assert!(s.max_rtt < 4.0);
+            assert!(s.mean_rtt <= s.max_rtt);
+        }
+    }
+
+    #[test]
+    fn kmeans_zones() {
+        let coords = zones();
+        let c = kmeans(&coords, 3, 100, &mut StdRng::seed_from_u64(1)).unwrap();
+        assert_zones(&c);
+        for (m, a) in c.medoids.iter().zip(0..) {
+            assert_eq!(c.assignments[*m], a);
+        }
+    }
+
+    #[test]
+    fn kmedoids_zones() {
+        let coords = zones();
+        let c = kmedoids(&coords, 3, 100, &mut StdRng::seed_from_u64(1)).unwrap();
+        assert_zones(&c);
+        for (m, center) in c.medoids.iter().zip(&c.centers) {
+            assert_eq!(coords[*m].raw_coord(), center.raw_coord());
+        }
+    }
+
+    #[test]
+    fn seeded_is_reproducible() {
+        let coords = zones();
+        let a = kmeans(&coords, 4, 100, &mut StdRng::seed_from_u64(9)).unwrap();
+        let b = kmeans(&coords, 4, 100, &mut StdRng::seed_from_u64(9)).unwrap();
+        assert_eq!(a.assignments, b.assignments);
+    }
+
+    #[test]
+    fn silhouette_picks_k() {
+        let coords = zones();
+        let score = |k| {
+            let c = kmedoids(&coords, k, 100, &mut StdRng::seed_from_u64(3)).unwrap();
+            silhouette(&coords, &c.assignments).unwrap()
+        };
+        let best = (2..=6)
+            .max_by(|a, b| score(*a).partial_cmp(&score(*b)).unwrap())
+            .unwrap();
+        assert_eq!(best, 3);
+        assert_eq!(silhouette(&coords, &[0; 15]).unwrap(), 0.0);
+    }
+
+    #[test]
+    fn invalid_k() {
+        let coords = zones();
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(kmeans(&coords, 0, 10, &mut rng)
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(kmedoids(&coords, 16, 10, &mut rng)
+            .unwrap_err()
+            .is_invalid_length());
+    }
+
+    #[test]
+    fn dynamic_dimensions() {
+        use crate::dynamic::VecD;
+
+        let coords = vec![
+            Coord::from(VecD::from([0.0, 0.0])),
+            Coord::from(VecD::from([0.1, 0.0])),
+            Coord::from(VecD::from([10.0, 10.0, 0.0])),
+        ];
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(kmeans(&coords, 2, 10, &mut rng)
+            .unwrap_err()
+            .is_dimension_mismatch());
+        assert!(kmedoids(&coords, 2, 10, &mut rng)
+            .unwrap_err()
+            .is_dimension_mismatch());
+    }
+
+    #[test]
+    fn overlapping_coords() {
+        let coords = vec![Coord::<VecD<2>>::new(); 4];
+        let c = kmeans(&coords, 2, 10, &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(c.assignments.len(), 4);
+        assert_ne!(c.medoids[0], c.medoids[1]);
+    }
+}
@@ -1,13 +1,39 @@
-#[cfg(all(feature = "std", feature = "rand"))]
-use rand::distributions::Distribution;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "rand")]
+use rand::{distributions::Distribution, Rng};
 
 #[cfg(feature = "alloc")]
-use crate::VecD;
+use crate::{
+    dynamic,
+    rank::{self, RankConfig, Ranked},
+    std::borrow::Borrow,
+    VecD,
+};
 use crate::{
     error::{Error, ErrorKind, Result},
     heapless, Config, Vector, OVERLAP_THRESHOLD,
 };
 
+/// A distance estimate along with lower and upper bounds on the true distance,
+/// as returned by [`Coord::estimate_to`] and
+/// [`Node::estimate_to`](crate::Node::estimate_to)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Estimate<T = f64> {
+    /// The point estimate, the same value as `distance_to` returns
+    pub rtt: T,
+    /// The lower bound on the true distance
+    pub low: T,
+    /// The upper bound on the true distance. This is unbounded (`f64::INFINITY`
+    /// or `Duration::MAX`) when the combined relative error reaches `1.0`
+    pub high: T,
+}
+
+impl<T: PartialOrd> Estimate<T> {
+    /// Returns true if `rtt` falls within the bounds (inclusive)
+    pub fn contains(&self, rtt: &T) -> bool { *rtt >= self.low && *rtt <= self.high }
+}
+
 /// A network coordinate consisting of a dimensional vector, and some metadata
 #[derive(Debug)]
 pub struct Coord<T> {
@@ -21,8 +47,8 @@ pub struct Coord<T> {
     /// anomalies. Height is handled automatically as part of the update and
     /// adjustment calculations.
     pub(crate) height: f64,
-    /// Positive manual additions to distance calculations. Negative offsets are
-    /// ignored
+    /// Manual additions to distance calculations. Only negative when set with
+    /// [`Coord::set_signed_offset`]
     pub(crate) offset: f64,
 }
 
@@ -70,12 +96,27 @@ where
     /// useful so that coordinates don't start out overlapping one another.
     #[cfg(all(feature = "std", feature = "rand"))]
     #[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "rand"))))]
-    pub fn rand() -> Self {
+    pub fn rand() -> Self { Self::rand_with_rng(&mut rand::thread_rng()) }
+
+    /// The same as [`Coord::rand`] but drawing from `rng`, so the coordinate
+    /// can be reproduced from a seed and created without `std`. Each component
+    /// is uniform within `[-1.0, 1.0)`.
+    ///
+    /// ```rust
+    /// use rand::{rngs::StdRng, SeedableRng};
+    /// use violin::{heapless::VecD, Coord};
+    ///
+    /// let a = Coord::<VecD<3>>::rand_with_rng(&mut StdRng::seed_from_u64(7));
+    /// let b = Coord::<VecD<3>>::rand_with_rng(&mut StdRng::seed_from_u64(7));
+    /// assert_eq!(a.raw_coord(), b.raw_coord());
+    /// ```
+    #[cfg(feature = "rand")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rand")))]
+    pub fn rand_with_rng<R: Rng + ?Sized>(rng: &mut R) -> Self {
         let mut vec = T::default();
-        let mut rng = rand::thread_rng();
         let die = rand::distributions::Uniform::from(-1.0..1.0);
         for n in vec.as_mut() {
-            *n = die.sample(&mut rng);
+            *n = die.sample(rng);
         }
 
         Self {
@@ -85,8 +126,9 @@ where
     }
 
     /// Estimate the distance between this coordinate and the other
-    /// coordinate's vector coordinate, adding any positive offset from
-    /// either coordinate
+    /// coordinate's vector coordinate, adding the offset from either
+    /// coordinate. Negative offsets can cancel out the heights, but the
+    /// estimate is never less than the distance between the vectors.
     #[cfg_attr(feature = "std", doc = "```rust")]
     #[cfg_attr(not(feature = "std"), doc = "```no_run")]
     /// use violin::{heapless::VecD, Coord};
@@ -99,7 +141,84 @@ where
     /// assert_eq!(c1.distance_to(&c2), 20.592458060283544);
     /// ```
     pub fn distance_to(&self, other: &Coord<T>) -> f64 {
-        self.raw_distance_to(other) + self.offset + other.offset
+        let vec_dist = self.vec.distance(&other.vec);
+        f64::max(
+            vec_dist + self.height + other.height + self.offset + other.offset,
+            vec_dist,
+        )
+    }
+
+    /// The same as [`Coord::distance_to`], but returns an error instead of
+    /// panicking if the coordinates have different dimensions
+    pub fn try_distance_to(&self, other: &Coord<T>) -> Result<f64> {
+        self.check_dimensions(other)?;
+        Ok(self.distance_to(other))
+    }
+
+    /// Estimate the distance between this coordinate and the other coordinate
+    /// along with bounds on the true distance derived from both coordinates'
+    /// error estimates.
+    ///
+    /// An error estimate is a moving average of the relative error
+    /// (`|estimate - rtt| / rtt`) of a coordinate's predictions. The pair's
+    /// relative error `r` is taken as the mean of both error estimates
+    /// multiplied by `k`, and the bounds are the range of distances that
+    /// `estimate` is within `r` relative error of: `estimate / (1 + r)` to
+    /// `estimate / (1 - r)`.
+    ///
+    /// If the error estimates accurately track the mean relative error, then by
+    /// Markov's inequality the bounds cover the true distance at least
+    /// `1 - 1/k` of the time (i.e. 50% for `k = 2.0`, 90% for `k = 10.0`). In
+    /// practice prediction errors are concentrated well below their mean so
+    /// coverage is considerably higher.
+    ///
+    /// A negative or NaN `k` is treated as `0.0`, so both bounds are the
+    /// estimate itself.
+    #[cfg_attr(feature = "std", doc = "```rust")]
+    #[cfg_attr(not(feature = "std"), doc = "```no_run")]
+    /// use violin::{heapless::VecD, Coord};
+    ///
+    /// let mut c1 = Coord::from(VecD::from([0.0, 0.0]));
+    /// let mut c2 = Coord::from(VecD::from([3.0, 4.0]));
+    /// c1.set_error_estimate(0.1);
+    /// c2.set_error_estimate(0.3);
+    ///
+    /// let est = c1.estimate_to(&c2, 2.0);
+    /// assert_eq!(est.rtt, 5.0);
+    /// assert_eq!(est.low, 5.0 / 1.4);
+    /// assert_eq!(est.high, 5.0 / 0.6);
+    /// assert!(est.contains(&6.0));
+    /// ```
+    pub fn estimate_to(&self, other: &Coord<T>, k: f64) -> Estimate {
+        let rtt = self.distance_to(other);
+        // `max` also turns a NaN into `0.0`
+        let r = f64::max(k * (self.error_estimate + other.error_estimate) / 2.0, 0.0);
+        Estimate {
+            rtt,
+            low: rtt / (1.0 + r),
+            high: if r < 1.0 {
+                rtt / (1.0 - r)
+            } else {
+                f64::INFINITY
+            },
+        }
+    }
+
+    /// Returns the `k` candidates with the lowest estimated distance from this
+    /// coordinate, closest first. See [`rank`](crate::rank) for how ties and
+    /// uncertain candidates are handled.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rank<I, C>(
+        &self,
+        candidates: impl IntoIterator<Item = (I, C)>,
+        k: usize,
+        cfg: &RankConfig,
+    ) -> Vec<Ranked<I>>
+    where
+        C: Borrow<Coord<T>>,
+    {
+        rank::rank(self, candidates, k, cfg)
     }
 
     /// Estimate the distance between this coordinate and the other
@@ -160,6 +279,10 @@ where
     /// reset the offset to `0.0`
     pub fn set_offset(&mut self, offset: f64) { self.offset = f64::max(0.0, offset); }
 
+    /// Set the raw offset, keeping negative offsets which lower distance
+    /// estimates (see [`Coord::distance_to`])
+    pub fn set_signed_offset(&mut self, offset: f64) { self.offset = offset; }
+
     /// Returns true of all values of the coordinates inner vector are neither
     /// NaN or Infinite
     pub fn is_finite(&self) -> bool { self.vec.as_ref().iter().all(|f| f.is_finite()) }
@@ -194,6 +317,7 @@ where
         threshold: f64,
         cfg: &Config,
     ) -> Result<()> {
+        self.check_dimensions(other)?;
         // TODO: dont go negative
         let low = rtt - threshold;
         let high = rtt + threshold;
@@ -261,20 +385,26 @@ where
     #[cfg(all(feature = "std", feature = "alloc"))]
     pub fn try_update_until_all<'a>(
         &mut self,
-        mut others: impl Iterator<Item = (f64, &'a Coord<T>)>,
+        others: impl Iterator<Item = (f64, &'a Coord<T>)>,
         threshold: f64,
         cfg: &Config,
     ) -> Result<()>
     where
         T: 'a,
     {
-        if !(self.error_estimate > 0.0 && others.all(|o| o.0 > 0.0 || o.1.error_estimate > 0.0)) {
+        let others: Vec<_> = others.collect();
+        for (_, other) in &others {
+            self.check_dimensions(other)?;
+        }
+        if !(self.error_estimate > 0.0
+            && others.iter().all(|o| o.0 > 0.0 || o.1.error_estimate > 0.0))
+        {
             return Err(Error {
                 kind: ErrorKind::InvalidCoordinate,
             });
         }
 
-        self.update_until_all(others, threshold, cfg);
+        self.update_until_all(others.into_iter(), threshold, cfg);
 
         Ok(())
     }
@@ -338,6 +468,7 @@ where
     /// asserting that it is less confident in the accuracy
     /// of it's coordinate position.)
     pub fn try_update(&mut self, rtt: f64, other: &Coord<T>, cfg: &Config) -> Result<()> {
+        self.check_dimensions(other)?;
         if !(self.error_estimate > 0.0 && other.error_estimate > 0.0 && rtt > 0.0) {
             return Err(Error {
                 kind: ErrorKind::InvalidCoordinate,
@@ -364,16 +495,92 @@ where
     /// - `rtt <= 0.0`
     /// - This coordinate's OR the other's error estimate `<= 0.0`
     pub fn update(&mut self, rtt: f64, other: &Coord<T>, cfg: &Config) {
-        assert!(self.error_estimate > 0.0 && other.error_estimate > 0.0 && rtt > 0.0);
+        self.update_weighted(rtt, other, 1.0, cfg);
+    }
+
+    /// The same as [`Coord::try_update`] but the impact of the sample on both
+    /// the coordinate and error estimate is scaled by a trust `weight`
+    ///
+    /// A `weight` of `1.0` is the same as [`Coord::try_update`] while `0.0`
+    /// skips the update entirely.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any:
+    ///
+    /// - `weight` is not within `[0.0, 1.0]`
+    /// - The coordinates have different dimensions
+    /// - `rtt <= 0.0` or either error estimate `<= 0.0`
+    pub fn try_update_weighted(
+        &mut self,
+        rtt: f64,
+        other: &Coord<T>,
+        weight: f64,
+        cfg: &Config,
+    ) -> Result<()> {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(Error {
+                kind: ErrorKind::InvalidWeight,
+            });
+        }
+        self.check_dimensions(other)?;
+        if !(self.error_estimate > 0.0 && other.error_estimate > 0.0 && rtt > 0.0) {
+            return Err(Error {
+                kind: ErrorKind::InvalidCoordinate,
+            });
+        }
+        if weight == 0.0 {
+            return Ok(());
+        }
+
+        self.update_weighted(rtt, other, weight, cfg);
+
+        Ok(())
+    }
+
+    /// The same as [`Coord::update`] but the impact of the sample on both the
+    /// coordinate and error estimate is scaled by a trust `weight`
+    ///
+    /// # Panics
+    ///
+    /// Panics if any:
+    ///
+    /// - `weight` is not within `[0.0, 1.0]`
+    /// - `rtt <= 0.0`
+    /// - This coordinate's OR the other's error estimate `<= 0.0`
+    pub fn update_weighted(&mut self, rtt: f64, other: &Coord<T>, weight: f64, cfg: &Config) {
+        assert!((0.0..=1.0).contains(&weight));
+        self.update_as(rtt, other, other.error_estimate, weight, cfg, None);
+    }
+
+    /// The same as [`Coord::update_weighted`] but using `other_error` in place
+    /// of the other's error estimate, and `uniform` (see
+    /// [`Vector::unit_vector_from_with`]) to pick a direction if the
+    /// coordinates overlap
+    pub(crate) fn update_as(
+        &mut self,
+        rtt: f64,
+        other: &Coord<T>,
+        other_error: f64,
+        weight: f64,
+        cfg: &Config,
+        uniform: Option<&mut dyn FnMut() -> f64>,
+    ) {
+        assert!(self.error_estimate > 0.0 && other_error > 0.0 && rtt > 0.0);
 
         // Sample weight balances local and other error
         //  - A high local error = greater movement
         //  - A high other error = less movement
-        let err_weight = self.error_estimate / (self.error_estimate + other.error_estimate);
+        let err_weight = self.error_estimate / (self.error_estimate + other_error) * weight;
 
-        // Compute relative error of this sample.
-        let dist = self.vec.distance(&other.vec);
-        let err = f64::max(dist - rtt, 0.0) / rtt;
+        // Compute relative error of this sample, using the same height vector
+        // distance the coordinates predict
+        let dist = self.raw_distance_to(other);
+        let err = if cfg.legacy_error {
+            f64::max(dist - rtt, 0.0) / rtt
+        } else {
+            f64::max(dist - rtt, rtt - dist) / rtt
+        };
 
         // Update weighted moving average of local error
         self.error_estimate =
@@ -382,24 +589,73 @@ where
         // Update local coordinates
         let delta = cfg.cc * err_weight;
         let force = delta * (rtt - dist);
-        self.apply_force_from(other, force, cfg);
+        let unit = match uniform {
+            Some(uniform) => self.vec.unit_vector_from_with(&other.vec, uniform),
+            None => self.vec.unit_vector_from(&other.vec),
+        };
+        self.apply_force(unit, force, other.height, cfg);
+    }
+
+    /// Returns how far [`Coord::update_as`] would move this coordinate, as the
+    /// distance moved by the vector plus the change in height
+    pub(crate) fn movement(
+        &self,
+        rtt: f64,
+        other: &Coord<T>,
+        other_error: f64,
+        weight: f64,
+        cfg: &Config,
+    ) -> f64 {
+        let err_weight = self.error_estimate / (self.error_estimate + other_error) * weight;
+        let dist = self.raw_distance_to(other);
+        let force = cfg.cc * err_weight * (rtt - dist);
+        // The force is split between the vector and height, see
+        // `Coord::apply_force`
+        crate::abs(force)
     }
 
     /// Gravity pulls the coordinate back toward the origin to prevent drift
     pub fn apply_gravity(&mut self, origin: &Coord<T>, cfg: &Config) {
         let dist = self.distance_to(origin);
         let rel_grav = dist / cfg.gravity_rho;
-        let force = -1.0 * (rel_grav * rel_grav);
+        let force = -(rel_grav * rel_grav);
         self.apply_force_from(origin, force, cfg);
     }
 
+    /// Vectors of different dimensions can't be compared, except for an empty
+    /// vector which is the origin of every dimension
+    pub(crate) fn check_dimensions(&self, other: &Coord<T>) -> Result<()> {
+        let (a, b) = (self.vec.as_ref().len(), other.vec.as_ref().len());
+        if a != b && a != 0 && b != 0 {
+            return Err(Error {
+                kind: ErrorKind::DimensionMismatch,
+            });
+        }
+        Ok(())
+    }
+
     fn apply_force_from(&mut self, other: &Coord<T>, force: f64, cfg: &Config) {
+        let unit = self.vec.unit_vector_from(&other.vec);
+        self.apply_force(unit, force, other.height, cfg);
+    }
+
+    /// Moves `force` along the height vector pointing away from the other
+    /// coordinate, `[uvec * mag, height + other_height]`, so the vector and
+    /// height each take their share of the distance between the two. This
+    /// changes the distance by exactly `force`, unless the height is held at
+    /// its minimum.
+    ///
+    /// Overlapping vectors only move the vector, otherwise the force would go
+    /// entirely to the height and the coordinates could never separate.
+    fn apply_force(&mut self, (mag, uvec): (f64, T), force: f64, other_height: f64, cfg: &Config) {
         self.height = f64::max(self.height, cfg.height_min);
-        let (mag, uvec) = self.vec.unit_vector_from(&other.vec);
-        self.vec += uvec * force;
         if mag > OVERLAP_THRESHOLD {
-            self.height = (self.height) + (force * (self.height / mag));
-            self.height = f64::max(self.height, cfg.height_min);
+            let heights = self.height + other_height;
+            let dist = mag + heights;
+            self.vec += uvec * (force * mag / dist);
+            self.height = f64::max(self.height + force * heights / dist, cfg.height_min);
+        } else {
+            self.vec += uvec * force;
         }
     }
 }
@@ -429,15 +685,33 @@ where
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<T> From<T> for Coord<dynamic::VecD>
+where
+    T: Into<dynamic::VecD>,
+{
+    fn from(vec: T) -> Self {
+        Self {
+            vec: vec.into(),
+            ..Default::default()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::close;
     #[cfg(not(feature = "alloc"))]
     use crate::heapless::VecD;
 
     #[test]
     fn apply_force_from() {
-        let cfg = Config::default();
+        // no heights, so only the vectors move
+        let cfg = Config {
+            height_min: 0.0,
+            ..Default::default()
+        };
         let mut origin = Coord::new();
         let above = Coord::from(VecD::from([0.0, 0.0, 2.9]));
         origin.apply_force_from(&above, 5.3, &cfg);
@@ -451,7 +725,11 @@ mod tests {
 
     #[test]
     fn unit_vec_div_zero() {
-        let cfg = Config::default();
+        // no heights, so only the vectors move
+        let cfg = Config {
+            height_min: 0.0,
+            ..Default::default()
+        };
         let mut c1 = Coord::<VecD<3>>::new();
         let c2 = Coord::new();
         c1.apply_force_from(&c2, 1.0, &cfg);
@@ -465,10 +743,14 @@ mod tests {
             ..Default::default()
         };
         let mut origin = Coord::new();
-        let above = Coord::from(VecD::from([0.0, 0.0, 2.9]));
-        origin.apply_force_from(&above, 5.3, &cfg);
-        assert_eq!(origin.raw_coord().as_ref(), &[0.0, 0.0, -5.3]);
-        assert_eq!(origin.height, 0.028_275_862_068_965_52);
+        let mut above = Coord::from(VecD::from([0.0, 0.0, 2.9]));
+        above.set_height(0.09);
+        // the force is split 2.9 / 3.0 to the vector and 0.1 / 3.0 to the
+        // height
+        origin.apply_force_from(&above, 6.0, &cfg);
+        assert_eq!(origin.raw_coord().as_ref(), &[0.0, 0.0, -5.8]);
+        assert_eq!(origin.height, 0.21);
+        assert_eq!(origin.raw_distance_to(&above), 3.0 + 6.0);
     }
 
     #[test]
@@ -478,12 +760,197 @@ mod tests {
             ..Default::default()
         };
         let mut origin = Coord::new();
-        let above = Coord::from(VecD::from([0.0, 0.0, 2.9]));
-        origin.apply_force_from(&above, -5.3, &cfg);
-        assert_eq!(origin.raw_coord().as_ref(), &[0.0, 0.0, 5.3]);
+        let mut above = Coord::from(VecD::from([0.0, 0.0, 2.9]));
+        above.set_height(0.1);
+        origin.apply_force_from(&above, -13.0, &cfg);
+        assert_eq!(
+            origin.raw_coord().as_ref(),
+            &[0.0, 0.0, 2.899_999_999_999_999_5]
+        );
         assert_eq!(origin.height, cfg.height_min);
     }
 
+    #[test]
+    fn update_uses_heights() {
+        let cfg = Config::default();
+        let mut other = Coord::new();
+        other.set_height(0.05);
+        other.set_error_estimate(1.0);
+        let mut c = Coord::from(VecD::from([0.1, 0.0, 0.0]));
+        c.set_height(0.05);
+        c.set_error_estimate(1.0);
+
+        // the vectors alone match the RTT, but the heights are predicted too
+        let before = c.raw_distance_to(&other);
+        c.update(0.1, &other, &cfg);
+        let after = c.raw_distance_to(&other);
+        assert!(after < before);
+        assert!(c.height() < 0.05);
+        assert!(c.raw_coord().as_ref()[0] < 0.1);
+    }
+
+    #[test]
+    fn update_weighted() {
+        let cfg = Config::default();
+        let other = Coord::from(VecD::from([1.0, 0.0, 0.0]));
+        let mut full = Coord::new();
+        full.set_error_estimate(1.0);
+        let mut unweighted = full.clone();
+        let mut half = full.clone();
+
+        full.update(3.0, &other, &cfg);
+        unweighted.update_weighted(3.0, &other, 1.0, &cfg);
+        half.update_weighted(3.0, &other, 0.5, &cfg);
+
+        assert_eq!(full.raw_coord(), unweighted.raw_coord());
+        assert_eq!(full.error_estimate(), unweighted.error_estimate());
+        assert!(half.distance_to(&Coord::new()) < full.distance_to(&Coord::new()));
+        assert!(half.error_estimate() > full.error_estimate());
+
+        assert!(half
+            .try_update_weighted(3.0, &other, 1.5, &cfg)
+            .unwrap_err()
+            .is_invalid_weight());
+        let before = half.clone();
+        half.try_update_weighted(3.0, &other, 0.0, &cfg).unwrap();
+        assert_eq!(half.raw_coord(), before.raw_coord());
+        assert_eq!(half.error_estimate(), before.error_estimate());
+    }
+
+    #[test]
+    fn error_estimate_tracks_error() {
+        // a fixed coordinate without heights so only the error estimate
+        // changes
+        let cfg = Config {
+            cc: 0.0,
+            height_min: 0.0,
+            ..Default::default()
+        };
+        let mut other = Coord::from(VecD::from([0.1, 0.0, 0.0]));
+        other.set_error_estimate(1.0e-6);
+        let settle = |rtt: f64, cfg: &Config| {
+            let mut c = Coord::new();
+            for _ in 0..200 {
+                c.update(rtt, &other, cfg);
+            }
+            c.error_estimate()
+        };
+
+        // underestimating and overestimating by half the RTT is the same error
+        assert!(close(settle(0.2, &cfg), 0.5));
+        assert!(close(settle(0.1 / 1.5, &cfg), 0.5));
+        assert!(close(settle(0.05, &cfg), 1.0));
+
+        let legacy = Config {
+            legacy_error: true,
+            ..cfg
+        };
+        assert!(settle(0.2, &legacy) < 1.0e-6);
+        assert!(close(settle(0.05, &legacy), 1.0));
+    }
+
+    #[cfg_attr(feature = "alloc", test)]
+    #[cfg(feature = "alloc")]
+    fn dimension_mismatch() {
+        let cfg = Config::default();
+        let mut a: Coord<dynamic::VecD> = Coord::new();
+        let b = Coord::from([0.1, 0.0, 0.0]);
+        let c = Coord::from([0.0, 0.2]);
+
+        // the empty default vector is the origin of any dimension
+        a.try_update(0.2, &b, &cfg).unwrap();
+        assert_eq!(a.raw_coord().dimensions(), 3);
+
+        let before = a.clone();
+        assert!(a
+            .try_update(0.2, &c, &cfg)
+            .unwrap_err()
+            .is_dimension_mismatch());
+        assert!(a.try_distance_to(&c).unwrap_err().is_dimension_mismatch());
+        #[cfg(feature = "std")]
+        assert!(a
+            .try_update_until_all([(0.2, &b), (0.2, &c)].into_iter(), 0.01, &cfg)
+            .unwrap_err()
+            .is_dimension_mismatch());
+        assert_eq!(a.raw_coord(), before.raw_coord());
+        assert_eq!(a.try_distance_to(&b), Ok(a.distance_to(&b)));
+    }
+
+    #[test]
+    fn estimate_invalid_k() {
+        let mut c1 = Coord::from(VecD::from([0.0, 0.0]));
+        let c2 = Coord::from(VecD::from([3.0, 4.0]));
+        c1.set_error_estimate(1.0);
+        let rtt = c1.distance_to(&c2);
+        for k in [-5.0, f64::NAN, f64::NEG_INFINITY] {
+            let est = c1.estimate_to(&c2, k);
+            assert_eq!((est.rtt, est.low, est.high), (rtt, rtt, rtt));
+        }
+    }
+
+    /// Embeds nodes with known RTTs and checks the bounds cover the true RTT at
+    /// least as often as documented
+    #[cfg_attr(feature = "std", test)]
+    #[cfg(feature = "std")]
+    fn estimate_calibration() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        const N: usize = 50;
+        let mut rng = StdRng::seed_from_u64(30);
+        let cfg = Config::default();
+
+        // Ground truth is a plane (up to 100ms across) plus access link delays
+        let truth: Vec<([f64; 2], f64)> = (0..N)
+            .map(|_| {
+                (
+                    [rng.gen_range(0.0..0.1), rng.gen_range(0.0..0.1)],
+                    rng.gen_range(0.0..0.01),
+                )
+            })
+            .collect();
+        let rtt = |a: usize, b: usize| {
+            let (pa, ha) = truth[a];
+            let (pb, hb) = truth[b];
+            ((pa[0] - pb[0]).powi(2) + (pa[1] - pb[1]).powi(2)).sqrt() + ha + hb
+        };
+
+        let mut coords: Vec<_> = (0..N)
+            .map(|_| {
+                let mut c = Coord::from(VecD::from([
+                    rng.gen_range(-0.01..0.01),
+                    rng.gen_range(-0.01..0.01),
+                    rng.gen_range(-0.01..0.01),
+                ]));
+                c.set_error_estimate(cfg.error_max);
+                c
+            })
+            .collect();
+        for _ in 0..200 {
+            for a in 0..N {
+                let b = (a + rng.gen_range(1..N)) % N;
+                // measurements carry +/-10% jitter
+                let sample = rtt(a, b) * rng.gen_range(0.9..1.1);
+                let other = coords[b].clone();
+                coords[a].update(sample, &other, &cfg);
+            }
+        }
+
+        for k in [2.0, 4.0, 10.0] {
+            let mut covered = 0;
+            let mut total = 0;
+            for a in 0..N {
+                for b in (0..N).filter(|b| *b != a) {
+                    total += 1;
+                    if coords[a].estimate_to(&coords[b], k).contains(&rtt(a, b)) {
+                        covered += 1;
+                    }
+                }
+            }
+            let rate = covered as f64 / total as f64;
+            assert!(rate >= 1.0 - 1.0 / k, "k = {k} covered {rate}");
+        }
+    }
+
     #[test]
     fn distance_to() {
         let c1 = Coord::from(VecD::from([2.3, 3.2, 4.1]));
@@ -548,6 +1015,24 @@ mod tests {
         assert_eq!(c1.distance_to(&c2), 8.40820748);
     }
 
+    #[test]
+    fn distance_with_signed_offset() {
+        let mut c1 = Coord::from(VecD::from([0.3, 0.0, 0.0]));
+        let mut c2 = Coord::new();
+        c1.set_height(0.25);
+        c2.set_height(0.25);
+
+        c1.set_signed_offset(-0.125);
+        assert_eq!(c1.offset(), -0.125);
+        assert!(close(c1.distance_to(&c2), 0.675));
+        c2.set_signed_offset(-0.125);
+        assert!(close(c1.distance_to(&c2), 0.55));
+        // the heights are cancelled out, but not the vectors
+        c1.set_signed_offset(-10.0);
+        assert!(close(c1.distance_to(&c2), 0.3));
+        assert!(close(c2.distance_to(&c1), 0.3));
+    }
+
     #[test]
     fn distance_with_height() {
         let mut c1 = Coord::from(VecD::from([2.3, 3.2, 4.1]));
@@ -0,0 +1,264 @@
+//! Parsers for public latency datasets
+//!
+//! Published Vivaldi results were measured on the King and PlanetLab
+//! all-pairs matrices and the Meridian dataset. These parsers read those
+//! formats from a `&str` into an [`RttMatrix`] in seconds, which can then be
+//! replayed through [`Node`](crate::Node)s, e.g. with a
+//! [`Simulation`](crate::sim::Simulation), or scored with any set of
+//! [`Coord`](crate::Coord)s.
+//!
+//! Reading the file is left to the caller so the parsers work without `std`.
+//! Every format skips blank lines and lines starting with `#`, and treats
+//! negative values as missing measurements. Real datasets have plenty of
+//! holes; [`RttMatrix::symmetrize`] fills a missing direction from the other
+//! one, and [`RttMatrix::known`] counts what is left.
+//!
+//! | Format | Layout | Unit |
+//! |---|---|---|
+//! | [`parse_king`] | Square matrix, one whitespace separated row per line | microseconds |
+//! | [`parse_meridian`] | `i j rtt` per line with zero based indices, each pair listed once | microseconds |
+//! | [`parse_planetlab`] | `src dst rtt` per line with host names | milliseconds |
+//!
+//! ```rust
+//! use violin::dataset;
+//!
+//! let input = "\
+//! ## src dst rtt(ms)
+//! a.example.org b.example.org 12.5
+//! b.example.org a.example.org 12.1
+//! a.example.org c.example.org 40.0
+//! ";
+//! let mut data = dataset::parse_planetlab(input).unwrap();
+//! assert_eq!(
+//!     data.names,
+//!     ["a.example.org", "b.example.org", "c.example.org"]
+//! );
+//! assert_eq!(data.rtts.get(1, 0), Some(0.0121));
+//!
+//! // c never measured anything itself
+//! assert_eq!(data.rtts.get(2, 0), None);
+//! data.rtts.symmetrize();
+//! assert_eq!(data.rtts.get(2, 0), Some(0.040));
+//! ```
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    matrix::RttMatrix,
+};
+
+// Dividing rounds once, unlike multiplying by an inexact 1.0e-3
+const MICROS: f64 = 1.0e6;
+const MILLIS: f64 = 1.0e3;
+
+/// A parsed dataset whose nodes are identified by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    /// The RTTs between nodes, in seconds
+    pub rtts: RttMatrix,
+    /// The name of each node, in order of first appearance
+    pub names: Vec<String>,
+}
+
+/// Parses a King-style all-pairs matrix
+///
+/// Each line is one row of a square matrix of RTTs in microseconds, separated
+/// by whitespace. The diagonal is always `0.0`.
+///
+/// # Errors
+///
+/// Returns an error if a value is not a number, or the rows are not all as
+/// long as there are rows
+pub fn parse_king(input: &str) -> Result<RttMatrix> {
+    let rows: Vec<Vec<f64>> = lines(input)
+        .map(|l| l.split_whitespace().map(number).collect())
+        .collect::<Result<_>>()?;
+
+    let n = rows.len();
+    if rows.iter().any(|r| r.len() != n) {
+        return Err(invalid());
+    }
+    Ok(RttMatrix::from_fn(n, |i, j| Some(rows[i][j] / MICROS)))
+}
+
+/// Parses the Meridian dataset
+///
+/// Each line is `i j rtt` where `i` and `j` are zero based node indices and
+/// `rtt` is in microseconds. As the dataset lists each pair only once, the
+/// result is [symmetrized](RttMatrix::symmetrize). The number of nodes is one
+/// more than the largest index. As every node must appear on some line, the
+/// indices must be less than twice the number of lines, which keeps a
+/// malformed index from allocating a huge matrix.
+///
+/// # Errors
+///
+/// Returns an error if a line does not have exactly three fields, an index is
+/// not a non-negative integer or is out of range, or an RTT is not a number
+pub fn parse_meridian(input: &str) -> Result<RttMatrix> {
+    let mut edges = Vec::new();
+    for line in lines(input) {
+        let (a, b, rtt) = triple(line)?;
+        let a = a.parse::<usize>().map_err(|_| invalid())?;
+        let b = b.parse::<usize>().map_err(|_| invalid())?;
+        edges.push((a, b, number(rtt)?));
+    }
+
+    let limit = edges.len().saturating_mul(2);
+    let n = edges
+        .iter()
+        .map(|(a, b, _)| match a.max(b).checked_add(1) {
+            Some(n) if n <= limit => Ok(n),
+            _ => Err(invalid()),
+        })
+        .try_fold(0, |n, m| m.map(|m| n.max(m)))?;
+    let mut rtts = RttMatrix::from_fn(n, |_, _| None);
+    for (a, b, rtt) in edges {
+        set_min(&mut rtts, a, b, rtt / MICROS);
+    }
+    rtts.symmetrize();
+    Ok(rtts)
+}
+
+/// Parses PlanetLab all-pairs pings
+///
+/// Each line is `src dst rtt` where `src` and `dst` are host names (or any
+/// other whitespace free identifier) and `rtt` is in milliseconds. Pairs
+/// measured more than once keep their lowest RTT.
+///
+/// # Errors
+///
+/// Returns an error if a line does not have exactly three fields or an RTT is
+/// not a number
+pub fn parse_planetlab(input: &str) -> Result<Dataset> {
+    let mut names: Vec<String> = Vec::new();
+    let mut index = |name: &str| match names.iter().position(|n| n == name) {
+        Some(i) => i,
+        None => {
+            names.push(name.to_string());
+            names.len() - 1
+        }
+    };
+
+    let mut edges = Vec::new();
+    for line in lines(input) {
+        let (a, b, rtt) = triple(line)?;
+        edges.push((index(a), index(b), number(rtt)?));
+    }
+
+    let mut rtts = RttMatrix::from_fn(names.len(), |_, _| None);
+    for (a, b, rtt) in edges {
+        set_min(&mut rtts, a, b, rtt / MILLIS);
+    }
+    Ok(Dataset { rtts, names })
+}
+
+/// Returns the lines holding data
+fn lines(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+}
+
+fn triple(line: &str) -> Result<(&str, &str, &str)> {
+    let mut fields = line.split_whitespace();
+    match (fields.next(), fields.next(), fields.next(), fields.next()) {
+        (Some(a), Some(b), Some(rtt), None) => Ok((a, b, rtt)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses an RTT, where negative values mean missing
+fn number(s: &str) -> Result<f64> {
+    let v = s.parse::<f64>().map_err(|_| invalid())?;
+    if v.is_nan() {
+        return Err(invalid());
+    }
+    Ok(if v < 0.0 { f64::NAN } else { v })
+}
+
+/// Keeps the lowest RTT seen for a pair. Setting a NaN leaves it missing.
+fn set_min(rtts: &mut RttMatrix, a: usize, b: usize, rtt: f64) {
+    if a != b {
+        // `f64::min` ignores the NaN of a missing entry
+        rtts.set(a, b, f64::min(rtts.get(a, b).unwrap_or(f64::NAN), rtt));
+    }
+}
+
+fn invalid() -> Error {
+    Error {
+        kind: ErrorKind::InvalidData,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::close;
+
+    #[test]
+    fn king() {
+        let input = "\
+            # header comment
+            0 1000 -1
+
+            1200 0 3000
+            2500 3100 0
+        ";
+        let m = parse_king(input).unwrap();
+        assert_eq!(m.len(), 3);
+        assert!(close(m.get(0, 1).unwrap(), 0.001));
+        assert!(close(m.get(1, 0).unwrap(), 0.0012));
+        assert_eq!(m.get(0, 2), None);
+        assert_eq!(m.get(1, 1), Some(0.0));
+        assert_eq!(m.known(), 5);
+
+        assert!(parse_king("0 1\n1 0 2").unwrap_err().is_invalid_data());
+        assert!(parse_king("0 x\n1 0").unwrap_err().is_invalid_data());
+        assert!(parse_king("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn meridian() {
+        let input = "0 1 5000\n1 2 7000\n0 3 -1\n";
+        let m = parse_meridian(input).unwrap();
+        assert_eq!(m.len(), 4);
+        assert!(close(m.get(1, 0).unwrap(), 0.005));
+        assert!(close(m.get(2, 1).unwrap(), 0.007));
+        assert_eq!(m.get(0, 3), None);
+        assert_eq!(m.get(0, 2), None);
+        assert_eq!(m.known(), 4);
+
+        assert!(parse_meridian("0 1").unwrap_err().is_invalid_data());
+        assert!(parse_meridian("0 -1 10").unwrap_err().is_invalid_data());
+        assert!(parse_meridian("0 1 10 20").unwrap_err().is_invalid_data());
+    }
+
+    #[test]
+    fn meridian_index_bound() {
+        // an index that doesn't fit in the lines given is rejected before
+        // anything is allocated
+        let err = parse_meridian("0 4000000000 10").unwrap_err();
+        assert!(err.is_invalid_data());
+        let max = format!("{} 0 10", usize::MAX);
+        assert!(parse_meridian(&max).unwrap_err().is_invalid_data());
+        assert!(parse_meridian("0 3 10\n1 2 10\n").is_ok());
+        assert!(parse_meridian("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn planetlab_keeps_minimum() {
+        let input = "a b 20\na b 10.5\nb a 11\na a 1\n";
+        let d = parse_planetlab(input).unwrap();
+        assert_eq!(d.names, ["a", "b"]);
+        assert!(close(d.rtts.get(0, 1).unwrap(), 0.0105));
+        assert!(close(d.rtts.get(1, 0).unwrap(), 0.011));
+        assert_eq!(d.rtts.get(0, 0), Some(0.0));
+
+        assert!(parse_planetlab("a b NaN").unwrap_err().is_invalid_data());
+    }
+}
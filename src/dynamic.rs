@@ -0,0 +1,230 @@
+//! Defines the `VecD` coordinate vector whose dimension is chosen at runtime
+
+use alloc::vec::Vec;
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    heapless,
+    std::{
+        convert::TryFrom,
+        ops::{Add, AddAssign, Div, Mul},
+    },
+    Vector,
+};
+
+/// A `VecD` is a coordinate vector made up of any number of `f64`s stored in
+/// a `Vec`, so the dimension can come from configuration or a peer
+///
+/// The empty vector returned by `VecD::default()` is treated as the origin of
+/// every dimension, so freshly created coordinates, gravity and accumulations
+/// starting from `Default` work without knowing the dimension up front. Any
+/// other pair of vectors must have the same dimension. The operator traits
+/// (`+`, [`Vector::difference`] and so on) panic when they don't, while the
+/// fallible coordinate and node APIs such as
+/// [`Coord::try_update`](crate::Coord::try_update) return an error for which
+/// [`Error::is_dimension_mismatch`] is true.
+///
+/// [`Vector::LEN`] is `0` as the length is not known at compile time, use
+/// [`VecD::dimensions`] instead. Fixed size vectors convert from
+/// `VecD::from(v.as_ref())` and back with `TryFrom`.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use violin::{dynamic::VecD, Coord, Node};
+///
+/// // the dimension could be read from a config file
+/// let dimensions = 3;
+/// let mut node = Node::<VecD>::with_coord(VecD::zeros(dimensions));
+/// let b = Coord::from(vec![0.1, 0.2, 0.2]);
+/// node.try_update(Duration::from_millis(500), &b).unwrap();
+///
+/// let c = Coord::from(VecD::from([0.1, 0.2]));
+/// assert!(node
+///     .try_update(Duration::from_millis(500), &c)
+///     .unwrap_err()
+///     .is_dimension_mismatch());
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct VecD {
+    inner: Vec<f64>,
+}
+
+impl VecD {
+    /// Creates a vector at the origin of `dimensions` dimensions
+    pub fn zeros(dimensions: usize) -> Self {
+        Self {
+            inner: alloc::vec![0.0; dimensions],
+        }
+    }
+
+    /// Returns the number of dimensions
+    pub fn dimensions(&self) -> usize { self.inner.len() }
+
+    /// Returns the components, e.g. to serialize them
+    pub fn into_inner(self) -> Vec<f64> { self.inner }
+
+    /// Applies `f` to each pair of components, treating an empty vector as
+    /// the origin
+    fn zip_with(&self, other: &Self, f: impl Fn(f64, f64) -> f64) -> Self {
+        let n = match (self.dimensions(), other.dimensions()) {
+            (a, 0) | (0, a) => a,
+            (a, b) if a == b => a,
+            (a, b) => panic!("dimension mismatch: {} != {}", a, b),
+        };
+        let get = |v: &Self, i: usize| v.inner.get(i).copied().unwrap_or(0.0);
+        Self {
+            inner: (0..n).map(|i| f(get(self, i), get(other, i))).collect(),
+        }
+    }
+}
+
+impl Vector for VecD {
+    const LEN: usize = 0;
+
+    fn difference(&self, other: &Self) -> Self { self.zip_with(other, |s, o| s - o) }
+
+    fn magnitude2(&self) -> f64 { self.inner.iter().map(|n| n * n).sum() }
+}
+
+impl From<Vec<f64>> for VecD {
+    fn from(inner: Vec<f64>) -> Self { Self { inner } }
+}
+
+impl From<&[f64]> for VecD {
+    fn from(s: &[f64]) -> Self { Self { inner: s.to_vec() } }
+}
+
+impl<const N: usize> From<[f64; N]> for VecD {
+    fn from(arr: [f64; N]) -> Self {
+        Self {
+            inner: arr.to_vec(),
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<VecD> for heapless::VecD<N> {
+    type Error = Error;
+
+    /// Fails unless the vector has exactly `N` dimensions
+    fn try_from(v: VecD) -> Result<Self> {
+        let mut ret = Self::default();
+        if v.dimensions() != N {
+            return Err(Error {
+                kind: ErrorKind::DimensionMismatch,
+            });
+        }
+        ret.as_mut().copy_from_slice(&v.inner);
+        Ok(ret)
+    }
+}
+
+impl Add<VecD> for VecD {
+    type Output = VecD;
+
+    fn add(self, rhs: Self) -> Self::Output { self.zip_with(&rhs, |s, r| s + r) }
+}
+
+impl AsRef<[f64]> for VecD {
+    fn as_ref(&self) -> &[f64] { &self.inner }
+}
+
+impl AsMut<[f64]> for VecD {
+    fn as_mut(&mut self) -> &mut [f64] { &mut self.inner }
+}
+
+impl AddAssign<VecD> for VecD {
+    fn add_assign(&mut self, rhs: VecD) { *self = self.zip_with(&rhs, |s, r| s + r); }
+}
+
+impl Mul<f64> for VecD {
+    type Output = VecD;
+
+    fn mul(mut self, rhs: f64) -> Self {
+        self.inner.iter_mut().for_each(|n| *n *= rhs);
+        self
+    }
+}
+
+impl Div<f64> for VecD {
+    type Output = VecD;
+
+    fn div(mut self, rhs: f64) -> Self {
+        self.inner.iter_mut().for_each(|n| *n /= rhs);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distance() {
+        let dist = VecD::from([1., 0., 5.]).distance(&VecD::from([0., 2., 4.]));
+        #[cfg(feature = "std")]
+        assert_eq!(dist, 2.449489742783178);
+        #[cfg(not(feature = "std"))]
+        assert_eq!(dist, 2.44948974);
+    }
+
+    #[test]
+    fn magnitude() {
+        assert_eq!(VecD::default().magnitude(), 0.0);
+        assert_eq!(VecD::zeros(4).magnitude(), 0.0);
+
+        #[cfg(feature = "std")]
+        assert_eq!(VecD::from([1.0, -2.0, 3.0]).magnitude(), 3.7416573867739413);
+        #[cfg(not(feature = "std"))]
+        assert_eq!(VecD::from([1.0, -2.0, 3.0]).magnitude(), 3.74165739);
+    }
+
+    #[test]
+    fn unit_vector() {
+        let (_, uv) = VecD::from([1., 0., 5.]).unit_vector_from(&VecD::from([0., 2., 4.]));
+        #[cfg(feature = "std")]
+        assert_eq!(
+            uv,
+            VecD::from([0.4082482904638631, -0.8164965809277261, 0.4082482904638631])
+        );
+        #[cfg(not(feature = "std"))]
+        assert_eq!(
+            uv,
+            VecD::from([0.408248290927726, -0.816496581855452, 0.408248290927726])
+        );
+
+        let a = VecD::from([1.0, 2.0, 3.0]);
+        let (mag, uv) = a.unit_vector_from(&a);
+        assert_eq!(uv.magnitude(), 1.0);
+        assert_eq!(uv.dimensions(), 3);
+        assert_eq!(mag, 0.0);
+    }
+
+    #[test]
+    fn empty_is_origin() {
+        let a = VecD::from([1.0, -3.0, 3.0]);
+        assert_eq!(a.clone() + VecD::default(), a);
+        assert_eq!(VecD::default().difference(&a), a.clone() * -1.0);
+
+        let mut sum = VecD::default();
+        sum += a.clone();
+        sum += a.clone();
+        assert_eq!(sum, a * 2.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "dimension mismatch")]
+    fn mismatch_panics() { let _ = VecD::from([1.0, 2.0]) + VecD::from([1.0, 2.0, 3.0]); }
+
+    #[test]
+    fn conversions() {
+        let h = heapless::VecD::from([1.0, 2.0]);
+        let d = VecD::from(h.as_ref());
+        assert_eq!(d.dimensions(), 2);
+        assert_eq!(heapless::VecD::<2>::try_from(d.clone()), Ok(h));
+        assert!(heapless::VecD::<3>::try_from(d.clone())
+            .unwrap_err()
+            .is_dimension_mismatch());
+        assert_eq!(d.into_inner(), [1.0, 2.0]);
+    }
+}
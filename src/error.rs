@@ -13,6 +13,18 @@ pub type Result<T> = StdResult<T, Error>;
 pub enum ErrorKind {
     /// A component of the coordinate vector became NaN or Infinite
     InvalidCoordinate,
+    /// An output buffer or input slice was not the required length
+    InvalidLength,
+    /// A weight was negative, NaN or Infinite, or all weights were zero
+    InvalidWeight,
+    /// A configuration value was out of range
+    InvalidConfig,
+    /// Input data could not be parsed or held an invalid value
+    InvalidData,
+    /// Two coordinate vectors had a different number of dimensions
+    DimensionMismatch,
+    /// A sample was rejected by a node's [`Policy`](crate::Policy)
+    Rejected,
 }
 
 /// The Violin error type
@@ -24,6 +36,28 @@ pub struct Error {
 impl Error {
     /// Returns true if the error is due to an invalid coordinate
     pub fn is_invalid(&self) -> bool { self.kind == ErrorKind::InvalidCoordinate }
+
+    /// Returns true if the error is due to a buffer or slice of the wrong
+    /// length
+    pub fn is_invalid_length(&self) -> bool { self.kind == ErrorKind::InvalidLength }
+
+    /// Returns true if the error is due to an invalid weight
+    pub fn is_invalid_weight(&self) -> bool { self.kind == ErrorKind::InvalidWeight }
+
+    /// Returns true if the error is due to an out of range configuration value
+    pub fn is_invalid_config(&self) -> bool { self.kind == ErrorKind::InvalidConfig }
+
+    /// Returns true if the error is due to input data that could not be parsed
+    /// or held an invalid value
+    pub fn is_invalid_data(&self) -> bool { self.kind == ErrorKind::InvalidData }
+
+    /// Returns true if the error is due to coordinate vectors of different
+    /// dimensions
+    pub fn is_dimension_mismatch(&self) -> bool { self.kind == ErrorKind::DimensionMismatch }
+
+    /// Returns true if the error is due to a sample being rejected by a node's
+    /// policy
+    pub fn is_rejected(&self) -> bool { self.kind == ErrorKind::Rejected }
 }
 
 #[cfg(feature = "std")]
@@ -31,6 +65,12 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.kind {
             ErrorKind::InvalidCoordinate => write!(f, "invalid coordinate"),
+            ErrorKind::InvalidLength => write!(f, "invalid length"),
+            ErrorKind::InvalidWeight => write!(f, "invalid weight"),
+            ErrorKind::InvalidConfig => write!(f, "invalid configuration"),
+            ErrorKind::InvalidData => write!(f, "invalid data"),
+            ErrorKind::DimensionMismatch => write!(f, "dimension mismatch"),
+            ErrorKind::Rejected => write!(f, "sample rejected"),
         }
     }
 }
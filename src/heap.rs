@@ -1,7 +1,14 @@
-//! Defines the `VecD` coordinate vector that does use heap allocation
+//! Defines the `VecD` coordinate vector and adjustment `Window` that do use
+//! heap allocation
+
+use alloc::boxed::Box;
 
 use crate::{
-    std::ops::{Add, AddAssign, Div, Mul},
+    std::{
+        ops::{Add, AddAssign, Div, Mul},
+        time::Duration,
+    },
+    window::{self, Sample},
     Vector,
 };
 
@@ -47,26 +54,79 @@ impl<const N: usize> From<Box<[f64; N]>> for VecD<N> {
 
 impl_vec!(VecD<N>);
 
+/// A [`Window`](window::Window) of up to `N` adjustment samples stored as a
+/// boxed array, for windows too large to keep inline in a [`Node`](crate::Node)
+#[derive(Clone, PartialEq, Debug)]
+pub struct Window<const N: usize> {
+    buf: Box<[Sample; N]>,
+    len: usize,
+}
+
+impl<const N: usize> Default for Window<N> {
+    fn default() -> Self {
+        Self {
+            buf: Box::new([Sample::default(); N]),
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> window::Window for Window<N> {
+    fn capacity(&self) -> usize { N }
+
+    fn samples(&self) -> &[Sample] { &self.buf[..self.len] }
+
+    fn push(&mut self, sample: Sample) { window::push(&mut self.buf[..], &mut self.len, sample) }
+
+    fn expire(&mut self, cutoff: Duration) {
+        window::expire(&mut self.buf[..], &mut self.len, cutoff)
+    }
+
+    fn clear(&mut self) { self.len = 0; }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::window::{Aggregate, Window as _};
+
+    #[test]
+    fn window() {
+        let mut w = Window::<2>::default();
+        for (i, residual) in [1.0, 2.0, 4.0].into_iter().enumerate() {
+            w.push(Sample {
+                residual,
+                at: Duration::from_secs(i as u64),
+            });
+        }
+        assert_eq!(w.len(), 2);
+        assert_eq!(w.aggregate(Aggregate::Mean), 3.0);
+        w.expire(Duration::from_secs(2));
+        assert_eq!(w.aggregate(Aggregate::Median), 4.0);
+    }
 
     #[test]
     fn distance() {
-        assert_eq!(
-            VecD::from([1., 0., 5.]).distance(&VecD::from([0., 2., 4.])),
-            2.449489742783178
-        );
+        let dist = VecD::from([1., 0., 5.]).distance(&VecD::from([0., 2., 4.]));
+        #[cfg(feature = "std")]
+        assert_eq!(dist, 2.449489742783178);
+        #[cfg(not(feature = "std"))]
+        assert_eq!(dist, 2.44948974);
     }
 
     #[test]
     fn magnitude() {
         assert_eq!(VecD::<3>::default().magnitude(), 0.0);
-        assert_eq!(VecD::from([1.0, -2.0, 3.0]).magnitude(), 3.7416573867739413);
         assert_eq!(VecD::from([-2., 4., -4.]).magnitude(), 6.0f64);
+
+        #[cfg(feature = "std")]
+        assert_eq!(VecD::from([1.0, -2.0, 3.0]).magnitude(), 3.7416573867739413);
+        #[cfg(not(feature = "std"))]
+        assert_eq!(VecD::from([1.0, -2.0, 3.0]).magnitude(), 3.74165739);
     }
 
-    #[test]
+    #[cfg_attr(feature = "std", test)]
+    #[cfg(feature = "std")]
     fn unit_vector() {
         let (_, uv) = VecD::from([1., 0., 5.]).unit_vector_from(&VecD::from([0., 2., 4.]));
         assert_eq!(
@@ -86,6 +146,27 @@ mod tests {
         assert_eq!(mag, a.difference(&b).magnitude());
     }
 
+    #[cfg_attr(not(feature = "std"), test)]
+    #[cfg(not(feature = "std"))]
+    fn unit_vector_no_std() {
+        let (_, uv) = VecD::from([1., 0., 5.]).unit_vector_from(&VecD::from([0., 2., 4.]));
+        assert_eq!(
+            uv,
+            VecD::from([0.408248290927726, -0.816496581855452, 0.408248290927726])
+        );
+
+        let a = VecD::from([1.0, 2.0, 3.0]);
+        let b = VecD::from([0.5, 0.6, 0.7]);
+        let (mag, uv) = a.unit_vector_from(&b);
+        assert_eq!(
+            uv,
+            VecD::from([0.18257418567011074, 0.5112077198763101, 0.8398412540825093])
+        );
+        let uv_mag = uv.magnitude();
+        assert!(uv_mag > 0.9999999 && uv_mag <= 1.0);
+        assert_eq!(mag, a.difference(&b).magnitude());
+    }
+
     #[test]
     fn equal_unit_vectors() {
         // equal coordinates should not get a divide by zero
@@ -1,7 +1,12 @@
-//! Defines the `VecD` coordinate vector that does not use any heap allocation
+//! Defines the `VecD` coordinate vector and adjustment `Window` that do not
+//! use any heap allocation
 
 use crate::{
-    std::ops::{Add, AddAssign, Div, Mul},
+    std::{
+        ops::{Add, AddAssign, Div, Mul},
+        time::Duration,
+    },
+    window::{self, Sample},
     Vector,
 };
 
@@ -35,6 +40,35 @@ impl<const N: usize> From<[f64; N]> for VecD<N> {
 
 impl_vec!(VecD<N>);
 
+/// A [`Window`](window::Window) of up to `N` adjustment samples stored as an
+/// array
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Window<const N: usize> {
+    buf: [Sample; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for Window<N> {
+    fn default() -> Self {
+        Self {
+            buf: [Sample::default(); N],
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> window::Window for Window<N> {
+    fn capacity(&self) -> usize { N }
+
+    fn samples(&self) -> &[Sample] { &self.buf[..self.len] }
+
+    fn push(&mut self, sample: Sample) { window::push(&mut self.buf, &mut self.len, sample) }
+
+    fn expire(&mut self, cutoff: Duration) { window::expire(&mut self.buf, &mut self.len, cutoff) }
+
+    fn clear(&mut self) { self.len = 0; }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,7 +32,9 @@
 //! `no_std` and no `alloc` environments. Each coordinate is small consisting of
 //! a dimensional vector made up of an array of `f64`s. The arrays use const
 //! generics, so they can be as small as a single f64 or large as one needs.
-//! Although above a certain dimension there are diminishing returns.
+//! Although above a certain dimension there are diminishing returns. When the
+//! dimension is only known at runtime, `dynamic::VecD` (requires the `alloc`
+//! feature) stores it in a `Vec` instead.
 //!
 //! Nodes can measure real latencies between an origin node, or each-other to
 //! adjust their coordinates in space.
@@ -234,23 +236,66 @@ use crate::std::{
 
 #[macro_use]
 mod macros;
+pub mod center;
+#[cfg(all(feature = "alloc", feature = "rand"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "rand"))))]
+pub mod cluster;
 mod coord;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod dataset;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod dynamic;
 pub mod error;
 #[cfg(feature = "alloc")]
 #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
 pub mod heap;
 pub mod heapless;
+pub mod matrix;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod metrics;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod newton;
 mod node;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod placement;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod project;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod rank;
+pub mod route;
+#[cfg(all(feature = "alloc", feature = "rand"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "rand"))))]
+pub mod sim;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod tiv;
+#[cfg(all(feature = "alloc", feature = "rand"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "rand"))))]
+pub mod topology;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod trust;
+#[cfg(all(feature = "alloc", feature = "rand"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "alloc", feature = "rand"))))]
+pub mod tune;
+pub mod window;
 
-pub use coord::Coord;
+pub use coord::{Coord, Estimate};
 #[cfg(feature = "alloc")]
 #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
 pub use heap::VecD;
-pub use node::{Config, Node};
+pub use node::{Config, Node, Overlap, Policy, PolicyStats};
 
 /// Determines at what threshold two coordinates overlap
 const OVERLAP_THRESHOLD: f64 = 1.0e-6;
-const DEFAULT_HEIGHT_MIN: f64 = 0.0;
+const DEFAULT_HEIGHT_MIN: f64 = 1.0e-5;
 
 /// The abstraction over coordinate vectors
 pub trait Vector:
@@ -264,7 +309,8 @@ pub trait Vector:
 where
     Self: Sized,
 {
-    /// The length of the vector
+    /// The length of the vector, or `0` if it is only known at runtime (see
+    /// [`dynamic::VecD`](crate::dynamic::VecD))
     const LEN: usize;
 
     /// Returns a unit vector (`â`) from `other` pointing at `self` along
@@ -272,15 +318,62 @@ where
     fn unit_vector_from(&self, other: &Self) -> (f64, Self) {
         let diff = self.difference(other);
         let mag = diff.magnitude();
-        // If the coordinates overlap return a unit vector in the first dimension
+        // If the coordinates overlap return a unit vector in the first
+        // dimension
         if mag < OVERLAP_THRESHOLD {
-            let mut ret = Self::default();
-            ret.as_mut()[0] = 1.0;
+            let mut ret = diff * 0.0;
+            if let Some(n) = ret.as_mut().first_mut() {
+                *n = 1.0;
+            }
             return (0.0, ret);
         }
         (mag, diff * (1. / mag))
     }
 
+    /// The same as [`Vector::unit_vector_from`], except that when the
+    /// coordinates overlap the unit vector points in a random direction.
+    /// `uniform` must return values uniform within `[-1.0, 1.0)`.
+    ///
+    /// As in Serf, each component is drawn from `uniform` and the result is
+    /// normalized.
+    #[cfg_attr(feature = "std", doc = "```rust")]
+    #[cfg_attr(not(feature = "std"), doc = "```no_run")]
+    /// use violin::{heapless::VecD, Vector};
+    ///
+    /// let a = VecD::from([1.0, 2.0, 3.0]);
+    /// let mut values = [0.5, -0.5, 0.0].into_iter();
+    /// let (mag, uv) = a.unit_vector_from_with(&a, || values.next().unwrap());
+    /// assert_eq!(mag, 0.0);
+    /// assert_eq!(
+    ///     uv,
+    ///     VecD::from([0.7071067811865475, -0.7071067811865475, 0.0])
+    /// );
+    /// ```
+    fn unit_vector_from_with<F: FnMut() -> f64>(
+        &self,
+        other: &Self,
+        mut uniform: F,
+    ) -> (f64, Self) {
+        let diff = self.difference(other);
+        let mag = diff.magnitude();
+        if mag < OVERLAP_THRESHOLD {
+            let mut ret = diff * 0.0;
+            // Retry in the unlikely case every component is (nearly) zero,
+            // falling back to the first dimension
+            for _ in 0..8 {
+                for n in ret.as_mut() {
+                    *n = uniform();
+                }
+                let m = ret.magnitude();
+                if m > OVERLAP_THRESHOLD {
+                    return (0.0, ret * (1. / m));
+                }
+            }
+            return self.unit_vector_from(self);
+        }
+        (mag, diff * (1. / mag))
+    }
+
     /// Returns distance between `self` and `other`
     #[cfg_attr(feature = "std", doc = "```rust")]
     #[cfg_attr(not(feature = "std"), doc = "```no_run")]
@@ -346,23 +439,65 @@ where
     fn magnitude2(&self) -> f64;
 }
 
+/// Square root using the platform intrinsic when available, otherwise the
+/// hand written `no_std` version
+#[inline(always)]
+pub(crate) fn sqrt(n: f64) -> f64 {
+    #[cfg(feature = "std")]
+    {
+        n.sqrt()
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        _sqrt(n)
+    }
+}
+
+/// Absolute value, as `f64::abs` is not available in `core` on older
+/// compilers
+#[inline(always)]
+pub(crate) fn abs(n: f64) -> f64 {
+    if n < 0.0 {
+        -n
+    } else {
+        n
+    }
+}
+
+/// Returns true if `a` and `b` are within `1.0e-6` of each other, which the
+/// `no_std` square root also stays within
+#[cfg(test)]
+pub(crate) fn close(a: f64, b: f64) -> bool { abs(a - b) < 1.0e-6 }
+
 #[cfg(not(feature = "std"))]
 const PRECISION_INC: f64 = 1.0e-8;
 #[cfg(not(feature = "std"))]
 const PRECISION_POW: f64 = 1.0e+8;
+// Each search takes at most ~1100 steps when it finds a match
+#[cfg(not(feature = "std"))]
+const SEARCH_STEPS: usize = 2048;
 
+/// Square root by searching for a value whose square rounds to the same 8
+/// decimal places as `n`
+///
+/// Inputs that round to zero or below, and those for which no such value
+/// exists, e.g. because they are too large for that rounding and either
+/// search runs out of steps, fall back to [`_newton_sqrt`].
 #[cfg(not(feature = "std"))]
 fn _sqrt(n: f64) -> f64 {
     if n == 0.0 {
         return 0.0;
     }
-    let mut ans;
+    let mut ans = f64::NAN;
     let mut last;
     let mut mid = n / 2.0;
     let mut top = n;
     let r_n = _round(n);
+    if r_n.is_nan() || r_n <= 0.0 {
+        return _newton_sqrt(n);
+    }
 
-    loop {
+    for _ in 0..SEARCH_STEPS {
         last = mid;
         let sq = _round(mid * mid);
         if sq == r_n {
@@ -379,15 +514,19 @@ fn _sqrt(n: f64) -> f64 {
             mid += 1.0;
         }
     }
+    if ans.is_nan() {
+        return _newton_sqrt(n);
+    }
 
-    ans = f64::max(ans - 1.0, 0.0);
+    let start = f64::max(ans - 1.0, 0.0);
+    ans = f64::NAN;
     mid = 0.5;
-    loop {
+    for _ in 0..SEARCH_STEPS {
         last = mid;
-        let sq = (mid + ans) * (mid + ans);
+        let sq = (mid + start) * (mid + start);
         let r_sq = _round(sq);
         if r_sq == r_n {
-            ans += mid;
+            ans = start + mid;
             break;
         }
         if sq > n {
@@ -401,6 +540,9 @@ fn _sqrt(n: f64) -> f64 {
             mid += PRECISION_INC;
         }
     }
+    if ans.is_nan() {
+        return _newton_sqrt(n);
+    }
 
     _round(ans)
 }
@@ -412,6 +554,11 @@ fn _round(n: f64) -> f64 { _ceil((n * PRECISION_POW) - 0.49999999) / PRECISION_P
 #[inline(always)]
 #[cfg(not(feature = "std"))]
 fn _ceil(n: f64) -> f64 {
+    // Every float from 2^52 up is already an integer, and would overflow the
+    // `u64` below
+    if n >= 4_503_599_627_370_496.0 {
+        return n;
+    }
     let n_int = n as u64;
     if n > n_int as f64 {
         (n_int + 1) as f64
@@ -420,6 +567,33 @@ fn _ceil(n: f64) -> f64 {
     }
 }
 
+/// Square root by Newton's method, starting from an estimate made by halving
+/// the exponent of `n`
+///
+/// After the first step every iterate is at or above the root and decreases,
+/// so stopping once it no longer does ends within one ulp of the root in a
+/// handful of steps for any input.
+#[cfg(not(feature = "std"))]
+fn _newton_sqrt(n: f64) -> f64 {
+    if n.is_nan() || n < 0.0 {
+        return f64::NAN;
+    }
+    if n == 0.0 || n == f64::INFINITY {
+        return n;
+    }
+    let mut x = f64::from_bits((n.to_bits() >> 1) + (1023 << 51));
+    x = (x + n / x) / 2.0;
+    // Subnormal inputs start further off and take more steps
+    for _ in 0..64 {
+        let next = (x + n / x) / 2.0;
+        if next >= x {
+            break;
+        }
+        x = next;
+    }
+    x
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(not(feature = "std"))]
@@ -436,4 +610,26 @@ mod tests {
         assert_eq!(_sqrt(0.0), 0.0);
         assert_eq!(_sqrt(0.009983505350056349), 0.0999175);
     }
+
+    #[cfg_attr(not(feature = "std"), test)]
+    #[cfg(not(feature = "std"))]
+    fn sqrt_terminates() {
+        // inputs the old bisection never finished on
+        for n in [
+            1.461697700590745e-9,
+            4.198544594490748e-22,
+            9.88900698392994e6,
+        ] {
+            let r = _sqrt(n);
+            assert!(close(r * r / n, 1.0));
+        }
+        assert!(_sqrt(-1.0).is_nan());
+        assert!(_sqrt(f64::NAN).is_nan());
+        assert_eq!(_sqrt(f64::INFINITY), f64::INFINITY);
+        assert_eq!(
+            _sqrt(f64::MIN_POSITIVE * f64::EPSILON),
+            f64::from_bits((1023 - 537) << 52)
+        );
+        assert_eq!(_sqrt(f64::MAX * 0.25), 0.5 * _sqrt(f64::MAX));
+    }
 }
@@ -0,0 +1,520 @@
+//! Bulk estimated RTT computation over sets of coordinates
+//!
+//! Filling an all-pairs matrix by calling [`Coord::distance_to`] in a double
+//! loop computes every vector distance twice and, for `heap::VecD`, allocates
+//! a temporary vector per pair. The functions in this module compute each
+//! vector distance once directly from the coordinate slices and write both
+//! `(i, j)` and `(j, i)` from it.
+//!
+//! Every entry written is bit-for-bit identical to the corresponding
+//! `coords[i].distance_to(&coords[j])`.
+//!
+//! ```rust
+//! use violin::{heapless::VecD, matrix, Coord};
+//!
+//! let coords = [
+//!     Coord::from(VecD::from([0.0, 0.0])),
+//!     Coord::from(VecD::from([3.0, 4.0])),
+//!     Coord::from(VecD::from([6.0, 8.0])),
+//! ];
+//!
+//! let mut out = [0.0; 9];
+//! matrix::distance_matrix(&coords, &mut out).unwrap();
+//! assert_eq!(out[1], coords[0].distance_to(&coords[1]));
+//!
+//! let mut upper = [0.0; matrix::upper_len(3)];
+//! matrix::distance_matrix_upper(&coords, &mut upper).unwrap();
+//! assert_eq!(upper[matrix::upper_index(3, 1, 2)], out[5]);
+//! ```
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+#[cfg(all(feature = "std", feature = "rayon"))]
+use rayon::prelude::*;
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    Coord, Vector,
+};
+
+/// Returns the number of entries in a packed upper-triangular matrix
+/// (including the diagonal) for `n` coordinates
+pub const fn upper_len(n: usize) -> usize { n * (n + 1) / 2 }
+
+/// Returns the index of `(i, j)` within a packed upper-triangular matrix of
+/// `n` coordinates. The arguments may be given in either order.
+///
+/// Rows are stored one after another, with row `i` holding columns `i..n`.
+pub const fn upper_index(n: usize, i: usize, j: usize) -> usize {
+    let (i, j) = if i <= j { (i, j) } else { (j, i) };
+    i * n - i * (i + 1) / 2 + j
+}
+
+/// Fills `out` with the row-major `N×N` matrix of estimated distances between
+/// every pair of `coords`, where `out[i * N + j]` is
+/// `coords[i].distance_to(&coords[j])`
+///
+/// # Errors
+///
+/// Returns an error if `out.len() != coords.len() * coords.len()` or the
+/// coordinates have different dimensions
+pub fn distance_matrix<T: Vector>(coords: &[Coord<T>], out: &mut [f64]) -> Result<()> {
+    let n = coords.len();
+    check_len(out, n * n)?;
+    check_dimensions(coords)?;
+
+    for (i, a) in coords.iter().enumerate() {
+        out[i * n + i] = estimate(a, a, vector_distance(a, a));
+        for (j, b) in coords.iter().enumerate().skip(i + 1) {
+            let dist = vector_distance(a, b);
+            out[i * n + j] = estimate(a, b, dist);
+            out[j * n + i] = estimate(b, a, dist);
+        }
+    }
+
+    Ok(())
+}
+
+/// Fills `out` with the packed upper-triangular matrix (including the
+/// diagonal) of estimated distances between every pair of `coords`. Use
+/// [`upper_len`] to size the buffer and [`upper_index`] to look up entries.
+///
+/// # Errors
+///
+/// Returns an error if `out.len() != upper_len(coords.len())` or the
+/// coordinates have different dimensions
+pub fn distance_matrix_upper<T: Vector>(coords: &[Coord<T>], out: &mut [f64]) -> Result<()> {
+    check_len(out, upper_len(coords.len()))?;
+    check_dimensions(coords)?;
+
+    let mut idx = 0;
+    for (i, a) in coords.iter().enumerate() {
+        for b in &coords[i..] {
+            out[idx] = estimate(a, b, vector_distance(a, b));
+            idx += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// The same as [`distance_matrix`] but computes rows in parallel
+///
+/// # Errors
+///
+/// Returns an error if `out.len() != coords.len() * coords.len()` or the
+/// coordinates have different dimensions
+#[cfg(all(feature = "std", feature = "rayon"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "rayon"))))]
+pub fn par_distance_matrix<T>(coords: &[Coord<T>], out: &mut [f64]) -> Result<()>
+where
+    T: Vector + Sync,
+{
+    let n = coords.len();
+    check_len(out, n * n)?;
+    check_dimensions(coords)?;
+    if n == 0 {
+        return Ok(());
+    }
+
+    // Each row is computed in full, so the lower triangle repeats the vector
+    // distances of the upper one, but no thread has to wait on another
+    out.par_chunks_mut(n).enumerate().for_each(|(i, row)| {
+        let a = &coords[i];
+        for (o, b) in row.iter_mut().zip(coords) {
+            *o = estimate(a, b, vector_distance(a, b));
+        }
+    });
+
+    Ok(())
+}
+
+/// The same as [`distance_matrix_upper`] but computes rows in parallel
+///
+/// # Errors
+///
+/// Returns an error if `out.len() != upper_len(coords.len())` or the
+/// coordinates have different dimensions
+#[cfg(all(feature = "std", feature = "rayon"))]
+#[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "rayon"))))]
+pub fn par_distance_matrix_upper<T>(coords: &[Coord<T>], out: &mut [f64]) -> Result<()>
+where
+    T: Vector + Sync,
+{
+    let n = coords.len();
+    check_len(out, upper_len(n))?;
+    check_dimensions(coords)?;
+
+    // Split the packed buffer into its (shrinking) rows so each can be handed
+    // to a different thread
+    let mut rows = Vec::with_capacity(n);
+    let mut rest = out;
+    for i in 0..n {
+        let (row, tail) = rest.split_at_mut(n - i);
+        rows.push((i, row));
+        rest = tail;
+    }
+
+    rows.into_par_iter().for_each(|(i, row)| {
+        let a = &coords[i];
+        for (o, b) in row.iter_mut().zip(&coords[i..]) {
+            *o = estimate(a, b, vector_distance(a, b));
+        }
+    });
+
+    Ok(())
+}
+
+/// A dense `N×N` matrix of RTTs in seconds, such as the ground truth used to
+/// evaluate coordinates, where any entry may be missing
+///
+/// Entries are directed, so `(i, j)` and `(j, i)` may differ or only one of
+/// them may be present.
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RttMatrix {
+    n: usize,
+    // Missing entries are NaN
+    rtts: Vec<f64>,
+}
+
+#[cfg(feature = "alloc")]
+impl RttMatrix {
+    /// Creates an `n×n` matrix with every entry missing
+    pub fn new(n: usize) -> Self {
+        Self {
+            n,
+            rtts: vec![f64::NAN; n * n],
+        }
+    }
+
+    /// Creates an `n×n` matrix with entry `(i, j)` set to `f(i, j)` for every
+    /// `i != j`. The diagonal is always `0.0`.
+    pub fn from_fn(n: usize, mut f: impl FnMut(usize, usize) -> Option<f64>) -> Self {
+        let mut m = Self::new(n);
+        for i in 0..n {
+            for j in 0..n {
+                let rtt = if i == j { Some(0.0) } else { f(i, j) };
+                if let Some(rtt) = rtt {
+                    m.set(i, j, rtt);
+                }
+            }
+        }
+        m
+    }
+
+    /// Creates a matrix of the estimated RTTs between every pair of `coords`
+    ///
+    /// # Panics
+    ///
+    /// If the coordinates have different dimensions
+    pub fn from_coords<T: Vector>(coords: &[Coord<T>]) -> Self {
+        Self::try_from_coords(coords).expect("coordinates have different dimensions")
+    }
+
+    /// The same as [`RttMatrix::from_coords`], but returns an error instead of
+    /// panicking if the coordinates have different dimensions
+    pub fn try_from_coords<T: Vector>(coords: &[Coord<T>]) -> Result<Self> {
+        let n = coords.len();
+        let mut rtts = vec![0.0; n * n];
+        distance_matrix(coords, &mut rtts)?;
+        Ok(Self { n, rtts })
+    }
+
+    /// Returns the number of nodes, i.e. `N`
+    pub fn len(&self) -> usize { self.n }
+
+    /// Returns true if the matrix has no nodes
+    pub fn is_empty(&self) -> bool { self.n == 0 }
+
+    /// Returns the RTT from `i` to `j`, if known
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` or `j` is out of bounds
+    pub fn get(&self, i: usize, j: usize) -> Option<f64> {
+        let rtt = self.rtts[self.index(i, j)];
+        if rtt.is_nan() {
+            None
+        } else {
+            Some(rtt)
+        }
+    }
+
+    /// Sets the RTT from `i` to `j`. Negative, NaN or Infinite RTTs are
+    /// treated as missing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` or `j` is out of bounds
+    pub fn set(&mut self, i: usize, j: usize, rtt: f64) {
+        let idx = self.index(i, j);
+        self.rtts[idx] = if rtt.is_finite() && rtt >= 0.0 {
+            rtt
+        } else {
+            f64::NAN
+        };
+    }
+
+    /// Removes the RTT from `i` to `j`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `i` or `j` is out of bounds
+    pub fn remove(&mut self, i: usize, j: usize) {
+        let idx = self.index(i, j);
+        self.rtts[idx] = f64::NAN;
+    }
+
+    /// Fills every missing `(i, j)` with `(j, i)` where that is known
+    pub fn symmetrize(&mut self) {
+        for i in 0..self.n {
+            for j in 0..self.n {
+                let (ij, ji) = (i * self.n + j, j * self.n + i);
+                if self.rtts[ij].is_nan() {
+                    self.rtts[ij] = self.rtts[ji];
+                }
+            }
+        }
+    }
+
+    /// Returns the number of known entries off the diagonal
+    pub fn known(&self) -> usize {
+        (0..self.n)
+            .flat_map(|i| (0..self.n).filter(move |j| *j != i).map(move |j| (i, j)))
+            .filter(|(i, j)| !self.rtts[i * self.n + j].is_nan())
+            .count()
+    }
+
+    fn index(&self, i: usize, j: usize) -> usize {
+        assert!(i < self.n && j < self.n);
+        i * self.n + j
+    }
+}
+
+fn check_len(out: &[f64], len: usize) -> Result<()> {
+    if out.len() != len {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+    Ok(())
+}
+
+/// Checks every coordinate against the first one that isn't the empty origin
+pub(crate) fn check_dimensions<T: Vector>(coords: &[Coord<T>]) -> Result<()> {
+    match coords.iter().find(|c| !c.vec.as_ref().is_empty()) {
+        Some(first) => coords.iter().try_for_each(|c| first.check_dimensions(c)),
+        None => Ok(()),
+    }
+}
+
+/// Mirrors `Vector::distance` without allocating an intermediate difference
+/// vector. An empty vector is the origin, as it is for `dynamic::VecD`.
+#[inline(always)]
+fn vector_distance<T: Vector>(a: &Coord<T>, b: &Coord<T>) -> f64 {
+    let (a, b) = (a.vec.as_ref(), b.vec.as_ref());
+    let mut term: f64 = 0.0;
+    if a.len() == b.len() {
+        for (s, r) in a.iter().zip(b.iter()) {
+            let n = s - r;
+            term += n * n;
+        }
+    } else {
+        let get = |v: &[f64], i: usize| v.get(i).copied().unwrap_or(0.0);
+        for i in 0..usize::max(a.len(), b.len()) {
+            let n = get(a, i) - get(b, i);
+            term += n * n;
+        }
+    }
+    crate::sqrt(term)
+}
+
+/// Adds heights and offsets in the same order as `Coord::distance_to`, never
+/// going below the vector distance
+#[inline(always)]
+fn estimate<T>(a: &Coord<T>, b: &Coord<T>, dist: f64) -> f64 {
+    f64::max(dist + a.height + b.height + a.offset + b.offset, dist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heapless::VecD;
+
+    fn coords() -> [Coord<VecD<3>>; 4] {
+        let mut c = [
+            Coord::from(VecD::from([2.3, 3.2, 4.1])),
+            Coord::from(VecD::from([4.5, -6.1, -4.1])),
+            Coord::from(VecD::from([0.2, -3.3, 1.1])),
+            Coord::from(VecD::from([3.232, 3.123, -3.4])),
+        ];
+        c[0].set_height(0.3);
+        c[1].set_height(1.7);
+        c[2].set_offset(0.11);
+        c[3].set_height(0.07);
+        c[3].set_offset(2.9);
+        c
+    }
+
+    #[test]
+    fn full_matches_distance_to() {
+        let c = coords();
+        let mut out = [0.0; 16];
+        distance_matrix(&c, &mut out).unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(out[i * 4 + j], c[i].distance_to(&c[j]));
+            }
+        }
+    }
+
+    #[test]
+    fn upper_matches_distance_to() {
+        let c = coords();
+        let mut out = [0.0; upper_len(4)];
+        distance_matrix_upper(&c, &mut out).unwrap();
+        for i in 0..4 {
+            for j in i..4 {
+                assert_eq!(out[upper_index(4, i, j)], c[i].distance_to(&c[j]));
+                assert_eq!(upper_index(4, i, j), upper_index(4, j, i));
+            }
+        }
+    }
+
+    #[test]
+    fn signed_offsets_match_distance_to() {
+        let mut c = coords();
+        c[0].set_signed_offset(-10.0);
+        c[2].set_signed_offset(-0.5);
+        let mut full = [0.0; 16];
+        distance_matrix(&c, &mut full).unwrap();
+        let mut upper = [0.0; upper_len(4)];
+        distance_matrix_upper(&c, &mut upper).unwrap();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(full[i * 4 + j], c[i].distance_to(&c[j]));
+                assert_eq!(upper[upper_index(4, i, j)], c[i].distance_to(&c[j]));
+            }
+        }
+        assert_eq!(full[1], c[0].raw_coord().distance(c[1].raw_coord()));
+    }
+
+    #[test]
+    fn wrong_len() {
+        let c = coords();
+        let mut out = [0.0; 15];
+        assert!(distance_matrix(&c, &mut out)
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(distance_matrix_upper(&c, &mut out)
+            .unwrap_err()
+            .is_invalid_length());
+    }
+
+    #[test]
+    fn empty() {
+        let c: [Coord<VecD<3>>; 0] = [];
+        assert!(distance_matrix(&c, &mut []).is_ok());
+        assert!(distance_matrix_upper(&c, &mut []).is_ok());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn heap_matches_distance_to() {
+        let mut c = vec![
+            Coord::from(crate::VecD::from([2.3, 3.2, 4.1])),
+            Coord::from(crate::VecD::from([4.5, -6.1, -4.1])),
+            Coord::from(crate::VecD::from([0.2, -3.3, 1.1])),
+        ];
+        c[0].set_height(0.3);
+        c[2].set_offset(0.11);
+        let mut out = vec![0.0; 9];
+        distance_matrix(&c, &mut out).unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_eq!(out[i * 3 + j], c[i].distance_to(&c[j]));
+            }
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn dynamic_dimensions() {
+        use crate::dynamic::VecD;
+
+        // an empty vector is the origin of every dimension
+        let c = vec![
+            Coord::from(VecD::from(vec![])),
+            Coord::from(VecD::from([3.0, 4.0])),
+            Coord::from(VecD::from([0.0, -4.0])),
+        ];
+        let mut out = vec![0.0; 9];
+        distance_matrix(&c, &mut out).unwrap();
+        let mut upper = vec![0.0; upper_len(3)];
+        distance_matrix_upper(&c, &mut upper).unwrap();
+        for i in 0..3 {
+            for j in 0..3 {
+                assert_eq!(out[i * 3 + j], c[i].distance_to(&c[j]));
+                assert_eq!(upper[upper_index(3, i, j)], out[i * 3 + j]);
+            }
+        }
+        assert_eq!(out[1], 5.0);
+
+        let mut c = c;
+        c.push(Coord::from(VecD::from([1.0, 2.0, 3.0])));
+        let mut out = vec![0.0; 16];
+        let err = distance_matrix(&c, &mut out).unwrap_err();
+        assert!(err.is_dimension_mismatch());
+        let mut upper = vec![0.0; upper_len(4)];
+        let err = distance_matrix_upper(&c, &mut upper).unwrap_err();
+        assert!(err.is_dimension_mismatch());
+        let err = RttMatrix::try_from_coords(&c).unwrap_err();
+        assert!(err.is_dimension_mismatch());
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn rtt_matrix() {
+        let mut m = RttMatrix::from_fn(3, |i, j| if i < j { Some(0.01) } else { None });
+        assert_eq!(m.len(), 3);
+        assert_eq!(m.get(1, 1), Some(0.0));
+        assert_eq!(m.get(0, 2), Some(0.01));
+        assert_eq!(m.get(2, 0), None);
+
+        m.set(2, 0, 0.02);
+        assert_eq!(m.get(2, 0), Some(0.02));
+        m.set(2, 0, -1.0);
+        assert_eq!(m.get(2, 0), None);
+        m.remove(0, 2);
+        assert_eq!(m.get(0, 2), None);
+        assert_eq!(m.known(), 2);
+        m.symmetrize();
+        assert_eq!(m.get(1, 0), Some(0.01));
+        assert_eq!(m.get(0, 2), None);
+        assert_eq!(m.known(), 4);
+
+        let c = coords();
+        let m = RttMatrix::from_coords(&c);
+        assert_eq!(m.get(1, 3), Some(c[1].distance_to(&c[3])));
+    }
+
+    #[cfg(all(feature = "std", feature = "rayon"))]
+    #[test]
+    fn par_matches_sequential() {
+        let c = coords();
+        let mut seq = [0.0; 16];
+        let mut par = [0.0; 16];
+        distance_matrix(&c, &mut seq).unwrap();
+        par_distance_matrix(&c, &mut par).unwrap();
+        assert_eq!(seq, par);
+
+        let mut seq = [0.0; upper_len(4)];
+        let mut par = [0.0; upper_len(4)];
+        distance_matrix_upper(&c, &mut seq).unwrap();
+        par_distance_matrix_upper(&c, &mut par).unwrap();
+        assert_eq!(seq, par);
+    }
+}
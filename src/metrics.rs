@@ -0,0 +1,450 @@
+//! Accuracy and stability metrics against a ground truth
+//!
+//! Every function compares the estimates of a set of [`Coord`]s with a ground
+//! truth [`RttMatrix`] whose node `i` is `coords[i]`. Pairs without a known
+//! RTT are skipped, so sparse datasets work as is.
+//!
+//! - [`relative_error`]: `|estimate - rtt| / rtt` over every pair
+//! - [`absolute_error`]: `|estimate - rtt|` in seconds over every pair
+//! - [`rank_loss`]: the relative rank loss (RRL) of each node, the fraction of
+//!   pairs of other nodes whose order by distance the estimates get backwards
+//! - [`closest_neighbor_loss`]: for each node, how much further its estimated
+//!   closest neighbor really is than its true closest neighbor
+//! - [`stability`]: how far coordinates move per second across a sequence of
+//!   snapshots
+//!
+//! The rank and neighbor metrics are from [Lua et al. 2005, On the accuracy of
+//! embeddings for Internet coordinate systems](https://doi.org/10.1145/1330107.1330109).
+//!
+//! Results are summarized as a [`Distribution`]. To evaluate a run over time,
+//! call [`accuracy`] on each snapshot, e.g. each
+//! [`Sample`](crate::sim::Sample) of a simulation with snapshots enabled.
+//!
+//! ```rust
+//! use violin::{heapless::VecD, matrix::RttMatrix, metrics, Coord};
+//!
+//! let truth = RttMatrix::from_coords(&[
+//!     Coord::from(VecD::from([0.0])),
+//!     Coord::from(VecD::from([0.010])),
+//!     Coord::from(VecD::from([0.020])),
+//! ]);
+//! // Node 2 is estimated at 25ms from node 0 instead of 20ms
+//! let coords = [
+//!     Coord::from(VecD::from([0.0])),
+//!     Coord::from(VecD::from([0.010])),
+//!     Coord::from(VecD::from([0.025])),
+//! ];
+//!
+//! let acc = metrics::accuracy(&truth, &coords).unwrap();
+//! assert_eq!(acc.relative.count, 6);
+//! assert_eq!(acc.relative.min, 0.0);
+//! // 15ms instead of 10ms between nodes 1 and 2
+//! assert!((acc.relative.max - 0.5).abs() < 1.0e-4);
+//! // Every node still orders its peers correctly
+//! assert_eq!(acc.rank_loss.max, 0.0);
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    matrix::{self, RttMatrix},
+    std::{cmp::Ordering, time::Duration},
+    Coord, Vector,
+};
+
+/// A summary of a set of values
+///
+/// Percentiles are linearly interpolated between the nearest ranks.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Distribution {
+    /// The number of values
+    pub count: usize,
+    /// The arithmetic mean
+    pub mean: f64,
+    /// The smallest value
+    pub min: f64,
+    /// The 50th percentile
+    pub median: f64,
+    /// The 90th percentile
+    pub p90: f64,
+    /// The 99th percentile
+    pub p99: f64,
+    /// The largest value
+    pub max: f64,
+}
+
+impl Distribution {
+    /// Summarizes `values`, ignoring any NaNs. Returns `None` if there are no
+    /// other values.
+    pub fn new(values: impl IntoIterator<Item = f64>) -> Option<Self> {
+        let mut v: Vec<f64> = values.into_iter().filter(|v| !v.is_nan()).collect();
+        if v.is_empty() {
+            return None;
+        }
+        v.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        Some(Self {
+            count: v.len(),
+            mean: v.iter().sum::<f64>() / v.len() as f64,
+            min: v[0],
+            median: percentile(&v, 0.5),
+            p90: percentile(&v, 0.9),
+            p99: percentile(&v, 0.99),
+            max: v[v.len() - 1],
+        })
+    }
+}
+
+/// The accuracy metrics of a single set of coordinates, see [`accuracy`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Accuracy {
+    /// See [`relative_error`]
+    pub relative: Distribution,
+    /// See [`absolute_error`]
+    pub absolute: Distribution,
+    /// See [`rank_loss`]
+    pub rank_loss: Distribution,
+    /// See [`closest_neighbor_loss`]
+    pub closest_neighbor_loss: Distribution,
+}
+
+/// Computes every accuracy metric at once, sharing the work between them
+///
+/// # Errors
+///
+/// Returns an error if `coords.len() != truth.len()`, the coordinates have
+/// different dimensions, or there is not enough known data for one of the
+/// metrics
+pub fn accuracy<T: Vector>(truth: &RttMatrix, coords: &[Coord<T>]) -> Result<Accuracy> {
+    let est = estimates(truth, coords)?;
+    Ok(Accuracy {
+        relative: relative(truth, &est)?,
+        absolute: absolute(truth, &est)?,
+        rank_loss: rank(truth, &est)?,
+        closest_neighbor_loss: neighbor(truth, &est)?,
+    })
+}
+
+/// Returns the distribution of `|estimate - rtt| / rtt` over every ordered pair
+/// of distinct nodes with a known, non-zero RTT
+///
+/// # Errors
+///
+/// Returns an error if `coords.len() != truth.len()`, the coordinates have
+/// different dimensions, or there are no such pairs
+pub fn relative_error<T: Vector>(truth: &RttMatrix, coords: &[Coord<T>]) -> Result<Distribution> {
+    relative(truth, &estimates(truth, coords)?)
+}
+
+/// Returns the distribution of `|estimate - rtt|`, in seconds, over every
+/// ordered pair of distinct nodes with a known RTT
+///
+/// # Errors
+///
+/// Returns an error if `coords.len() != truth.len()`, the coordinates have
+/// different dimensions, or there are no such pairs
+pub fn absolute_error<T: Vector>(truth: &RttMatrix, coords: &[Coord<T>]) -> Result<Distribution> {
+    absolute(truth, &estimates(truth, coords)?)
+}
+
+/// Returns the distribution of each node's relative rank loss
+///
+/// For node `c`, this is the fraction of pairs of other nodes `a` and `b`
+/// (with both RTTs from `c` known) where `a` is truly closer to `c` than `b`
+/// but estimated further, or vice versa. `0.0` means the estimates order every
+/// node's peers perfectly. This takes `O(N³)` time.
+///
+/// # Errors
+///
+/// Returns an error if `coords.len() != truth.len()`, the coordinates have
+/// different dimensions, or no node has such a pair
+pub fn rank_loss<T: Vector>(truth: &RttMatrix, coords: &[Coord<T>]) -> Result<Distribution> {
+    rank(truth, &estimates(truth, coords)?)
+}
+
+/// Returns the distribution of each node's closest neighbor loss
+///
+/// For node `c`, this is the true RTT to the peer estimated to be closest
+/// minus the true RTT to the truly closest peer, in seconds, considering only
+/// peers with a known RTT from `c`. `0.0` means the estimates pick the right
+/// (or an equally close) neighbor.
+///
+/// # Errors
+///
+/// Returns an error if `coords.len() != truth.len()`, the coordinates have
+/// different dimensions, or no node has a peer with a known RTT
+pub fn closest_neighbor_loss<T: Vector>(
+    truth: &RttMatrix,
+    coords: &[Coord<T>],
+) -> Result<Distribution> {
+    neighbor(truth, &estimates(truth, coords)?)
+}
+
+/// Returns how far coordinates move per second between each consecutive pair
+/// of `snapshots`
+///
+/// A node's movement is the distance between its old and new raw vectors plus
+/// the change in its height, the most any estimate involving it can have
+/// changed. Each entry of the result is the mean movement per second over
+/// every node, so a run settles as the values approach `0.0`.
+///
+/// Snapshots are `(time, coords)`, for example
+/// `samples.iter().map(|s| (s.time, &s.coords[..]))` for a simulation.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - The snapshots do not all have the same number of coordinates
+/// - A snapshot is not strictly later than the one before it
+pub fn stability<'a, T, I>(snapshots: I) -> Result<Vec<f64>>
+where
+    T: Vector + 'a,
+    I: IntoIterator<Item = (Duration, &'a [Coord<T>])>,
+{
+    let mut out = Vec::new();
+    let mut prev: Option<(Duration, &[Coord<T>])> = None;
+    for (time, coords) in snapshots {
+        if let Some((t0, old)) = prev {
+            if old.len() != coords.len() {
+                return Err(Error {
+                    kind: ErrorKind::InvalidLength,
+                });
+            }
+            if time <= t0 {
+                return Err(Error {
+                    kind: ErrorKind::InvalidData,
+                });
+            }
+            let secs = (time - t0).as_secs_f64();
+            let moved = old
+                .iter()
+                .zip(coords)
+                .map(|(a, b)| a.vec.distance(&b.vec) + crate::abs(a.height - b.height))
+                .sum::<f64>();
+            out.push(if coords.is_empty() {
+                0.0
+            } else {
+                moved / coords.len() as f64 / secs
+            });
+        }
+        prev = Some((time, coords));
+    }
+    Ok(out)
+}
+
+/// Returns the `p` percentile of the sorted, non-empty `sorted`
+pub(crate) fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let lo = rank as usize;
+    let frac = rank - lo as f64;
+    match sorted.get(lo + 1) {
+        Some(hi) if frac > 0.0 => sorted[lo] + (hi - sorted[lo]) * frac,
+        _ => sorted[lo],
+    }
+}
+
+/// The row-major `N×N` estimated RTTs
+struct Estimates {
+    n: usize,
+    rtts: Vec<f64>,
+}
+
+impl Estimates {
+    fn get(&self, i: usize, j: usize) -> f64 { self.rtts[i * self.n + j] }
+}
+
+fn estimates<T: Vector>(truth: &RttMatrix, coords: &[Coord<T>]) -> Result<Estimates> {
+    let n = coords.len();
+    if truth.len() != n {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+    let mut rtts = vec![0.0; n * n];
+    matrix::distance_matrix(coords, &mut rtts)?;
+    Ok(Estimates { n, rtts })
+}
+
+/// Every ordered pair of distinct nodes with a known RTT, and that RTT
+fn known(truth: &RttMatrix) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+    let n = truth.len();
+    (0..n)
+        .flat_map(move |i| (0..n).map(move |j| (i, j)))
+        .filter(|(i, j)| i != j)
+        .filter_map(move |(i, j)| truth.get(i, j).map(|rtt| (i, j, rtt)))
+}
+
+fn relative(truth: &RttMatrix, est: &Estimates) -> Result<Distribution> {
+    summarize(
+        known(truth)
+            .filter(|(_, _, rtt)| *rtt > 0.0)
+            .map(|(i, j, rtt)| crate::abs(est.get(i, j) - rtt) / rtt),
+    )
+}
+
+fn absolute(truth: &RttMatrix, est: &Estimates) -> Result<Distribution> {
+    summarize(known(truth).map(|(i, j, rtt)| crate::abs(est.get(i, j) - rtt)))
+}
+
+fn rank(truth: &RttMatrix, est: &Estimates) -> Result<Distribution> {
+    let n = est.n;
+    let mut peers = Vec::with_capacity(n);
+    summarize((0..n).map(|c| {
+        peers.clear();
+        peers.extend(
+            (0..n)
+                .filter(|p| *p != c)
+                .filter_map(|p| truth.get(c, p).map(|rtt| (rtt, est.get(c, p)))),
+        );
+        let mut pairs = 0usize;
+        let mut swapped = 0usize;
+        for (i, (ta, ea)) in peers.iter().enumerate() {
+            for (tb, eb) in &peers[i + 1..] {
+                pairs += 1;
+                if (ta - tb) * (ea - eb) < 0.0 {
+                    swapped += 1;
+                }
+            }
+        }
+        if pairs == 0 {
+            f64::NAN
+        } else {
+            swapped as f64 / pairs as f64
+        }
+    }))
+}
+
+fn neighbor(truth: &RttMatrix, est: &Estimates) -> Result<Distribution> {
+    let n = est.n;
+    summarize((0..n).map(|c| {
+        let mut closest = f64::INFINITY;
+        let mut predicted = (f64::INFINITY, f64::NAN);
+        for p in (0..n).filter(|p| *p != c) {
+            if let Some(rtt) = truth.get(c, p) {
+                closest = closest.min(rtt);
+                if est.get(c, p) < predicted.0 {
+                    predicted = (est.get(c, p), rtt);
+                }
+            }
+        }
+        // NaN when there was no known peer
+        predicted.1 - closest
+    }))
+}
+
+fn summarize(values: impl IntoIterator<Item = f64>) -> Result<Distribution> {
+    Distribution::new(values).ok_or(Error {
+        kind: ErrorKind::InvalidData,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heapless::VecD;
+
+    fn line(xs: &[f64]) -> Vec<Coord<VecD<1>>> {
+        xs.iter().map(|x| Coord::from(VecD::from([*x]))).collect()
+    }
+
+    #[test]
+    fn distribution() {
+        let d = Distribution::new((1..=101).map(|v| v as f64).chain([f64::NAN])).unwrap();
+        assert_eq!(d.count, 101);
+        assert_eq!(d.mean, 51.0);
+        assert_eq!(d.min, 1.0);
+        assert_eq!(d.median, 51.0);
+        assert_eq!(d.p90, 91.0);
+        assert_eq!(d.p99, 100.0);
+        assert_eq!(d.max, 101.0);
+
+        let d = Distribution::new([4.0, 1.0]).unwrap();
+        assert_eq!(d.median, 2.5);
+        assert!(Distribution::new([]).is_none());
+    }
+
+    #[test]
+    fn perfect_embedding() {
+        let coords = line(&[0.0, 1.0, 3.0, 7.0]);
+        let truth = RttMatrix::from_coords(&coords);
+        let acc = accuracy(&truth, &coords).unwrap();
+        assert_eq!(acc.relative.max, 0.0);
+        assert_eq!(acc.absolute.max, 0.0);
+        assert_eq!(acc.rank_loss.max, 0.0);
+        assert_eq!(acc.closest_neighbor_loss.max, 0.0);
+        assert_eq!(acc.relative.count, 12);
+        assert_eq!(acc.rank_loss.count, 4);
+    }
+
+    #[test]
+    fn errors() {
+        let truth = RttMatrix::from_coords(&line(&[0.0, 1.0, 2.0]));
+        // Node 2 is estimated at 4 instead of 2
+        let coords = line(&[0.0, 1.0, 4.0]);
+        let rel = relative_error(&truth, &coords).unwrap();
+        assert_eq!(rel.count, 6);
+        assert_eq!(rel.max, 2.0);
+        let abs = absolute_error(&truth, &coords).unwrap();
+        assert_eq!(abs.max, 2.0);
+        assert_eq!(abs.min, 0.0);
+    }
+
+    #[test]
+    fn ranks_and_neighbors() {
+        let truth = RttMatrix::from_coords(&line(&[0.0, 1.0, 2.0, 3.0]));
+        // Nodes 1 and 2 swap places
+        let coords = line(&[0.0, 2.0, 1.0, 3.0]);
+
+        let rrl = rank_loss(&truth, &coords).unwrap();
+        // Node 0 ranks (1, 2) backwards out of 3 pairs, and every other node
+        // also gets exactly one of its pairs backwards
+        assert_eq!(rrl.min, 1.0 / 3.0);
+        assert_eq!(rrl.max, 1.0 / 3.0);
+
+        let cnl = closest_neighbor_loss(&truth, &coords).unwrap();
+        // Node 0 picks node 2 (2 away) over node 1 (1 away)
+        assert_eq!(cnl.max, 1.0);
+    }
+
+    #[test]
+    fn missing_truth() {
+        let coords = line(&[0.0, 1.0, 2.0]);
+        let mut truth = RttMatrix::from_coords(&coords);
+        truth.remove(0, 1);
+        truth.remove(0, 2);
+        let acc = accuracy(&truth, &coords).unwrap();
+        assert_eq!(acc.relative.count, 4);
+        // Node 0 has no known peers at all
+        assert_eq!(acc.closest_neighbor_loss.count, 2);
+
+        assert!(accuracy(&RttMatrix::new(2), &line(&[0.0, 1.0]))
+            .unwrap_err()
+            .is_invalid_data());
+        assert!(accuracy(&truth, &line(&[0.0]))
+            .unwrap_err()
+            .is_invalid_length());
+    }
+
+    #[test]
+    fn movement() {
+        let mut b = line(&[3.0, 1.0]);
+        b[1].set_height(2.0);
+        let snapshots = [
+            (Duration::from_secs(0), line(&[0.0, 1.0])),
+            (Duration::from_secs(2), b),
+            (Duration::from_secs(3), line(&[3.0, 1.0])),
+        ];
+        let s = stability(snapshots.iter().map(|(t, c)| (*t, &c[..]))).unwrap();
+        // (3 + 2) / 2 nodes / 2s, then the height drops back in 1s
+        assert_eq!(s, vec![1.25, 1.0]);
+
+        let bad = [
+            (Duration::from_secs(1), line(&[0.0])),
+            (Duration::from_secs(1), line(&[0.0])),
+        ];
+        assert!(stability(bad.iter().map(|(t, c)| (*t, &c[..])))
+            .unwrap_err()
+            .is_invalid_data());
+    }
+}
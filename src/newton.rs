@@ -0,0 +1,457 @@
+//! Newton-style invariant checks against coordinate attacks
+//!
+//! A [`Node`] trusts whatever its peers report. [Newton: Securing Virtual
+//! Coordinates by Enforcing Physical Laws](https://doi.org/10.1109/ICDCS.2012.43)
+//! (Seibert et al.) observes that honest Vivaldi nodes obey physical
+//! invariants that lying peers break:
+//!
+//! - **Centroid at origin**: forces are equal and opposite so they sum to zero
+//!   and the centroid of all coordinates stays put. A report that drags the
+//!   centroid of the known peers away from the origin, far beyond the spread of
+//!   the peers themselves, is a [`Violation::Centroid`].
+//! - **Equal and opposite forces**: the force a peer exerts on this node is the
+//!   force this node exerts on it, and honest peers exert forces of a similar
+//!   scale. A sample whose force is an outlier compared to the recent forces
+//!   from every peer is a [`Violation::Force`].
+//! - **Movement follows force**: an honest peer only moves as far as the forces
+//!   on it push it. A peer whose coordinate jumps between reports by an
+//!   outlying distance compared to the recent movements of every peer is a
+//!   [`Violation::Movement`].
+//!
+//! Outliers are found with Tukey's fences, i.e. above `Q3 + fence × IQR`, over
+//! the recent history of accepted samples, so flagged samples and samples the
+//! node itself rejects never skew the history. A [`Newton`] sits beside a
+//! [`Node`] and routes each sample through [`Newton::update`], which ignores
+//! samples from flagged peers and exposes the flagged peer IDs.
+//!
+//! This is a wrapper rather than a [`Node`] option because a node never
+//! learns who its peers are, only their coordinates, while every check here
+//! needs a peer's previous report. The force and movement histories are shared
+//! by all peers rather than kept per peer: a per-peer history takes many
+//! samples from each peer before it can flag anything, and a liar that drifts
+//! slowly sets its own fences. Samples passed to [`Node::update`] directly,
+//! including those from a `sim::Simulation` or `tune`, are not checked.
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use violin::{
+//!     heapless::VecD,
+//!     newton::{Newton, NewtonConfig, Violation},
+//!     Coord, Node,
+//! };
+//!
+//! let mut node = Node::<VecD<2>>::new();
+//! let mut newton = Newton::new(NewtonConfig::default());
+//!
+//! // honest peers on a circle of 10ms around the origin
+//! let peers: Vec<_> = (0..8)
+//!     .map(|i| {
+//!         let a = i as f64 * std::f64::consts::PI / 4.0;
+//!         let mut c = Coord::from(VecD::from([0.01 * a.cos(), 0.01 * a.sin()]));
+//!         c.set_error_estimate(0.2);
+//!         c
+//!     })
+//!     .collect();
+//! for _ in 0..10 {
+//!     for (id, peer) in peers.iter().enumerate() {
+//!         newton
+//!             .update(&mut node, id, Duration::from_millis(10), peer)
+//!             .unwrap();
+//!     }
+//! }
+//!
+//! // a peer claiming perfect confidence from the other side of the world
+//! let mut liar = Coord::from(VecD::from([5.0, 5.0]));
+//! liar.set_error_estimate(1.0e-6);
+//! let before = node.coordinate().clone();
+//! assert!(newton
+//!     .update(&mut node, 99, Duration::from_millis(10), &liar)
+//!     .unwrap_err()
+//!     .is_rejected());
+//! assert_eq!(node.coordinate().raw_coord(), before.raw_coord());
+//! assert_eq!(newton.violation(&99), Some(Violation::Force));
+//! assert_eq!(newton.flagged().count(), 1);
+//! ```
+
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    metrics::percentile,
+    std::{cmp::Ordering, time::Duration},
+    window::Window,
+    Coord, Node, Vector,
+};
+
+/// Tunables for the invariant checks of a [`Newton`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NewtonConfig {
+    /// Values above `Q3 + fence × IQR` of the history are outliers
+    pub fence: f64,
+
+    /// Forces and movements up to this many seconds are never outliers, so
+    /// a quiet, converged network doesn't flag the first peer to move
+    pub tolerance: f64,
+
+    /// The number of recent accepted samples the fences are computed from
+    pub history: usize,
+
+    /// The force and movement checks start once this many samples are in
+    /// their history
+    pub warmup: usize,
+
+    /// A report is a [`Violation::Centroid`] if it moves the centroid of the
+    /// known peers further from the origin than this many times the peers'
+    /// mean distance from the origin
+    pub centroid_max: f64,
+
+    /// The centroid check starts once this many peers are known
+    pub min_peers: usize,
+}
+
+impl Default for NewtonConfig {
+    fn default() -> Self {
+        Self {
+            fence: 3.0,
+            tolerance: 0.001,
+            history: 64,
+            warmup: 16,
+            centroid_max: 1.0,
+            min_peers: 4,
+        }
+    }
+}
+
+/// The invariant a flagged peer violated
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// The report dragged the centroid of the known peers away from the origin
+    Centroid,
+    /// The force of the sample was an outlier
+    Force,
+    /// The peer's coordinate moved an outlying distance since its last report
+    Movement,
+}
+
+#[derive(Debug, Clone)]
+struct Peer<V> {
+    vec: V,
+    magnitude: f64,
+    violation: Option<Violation>,
+}
+
+/// A ring buffer of recent values
+#[derive(Debug, Clone)]
+struct History {
+    values: Vec<f64>,
+    next: usize,
+}
+
+impl History {
+    fn push(&mut self, value: f64, cap: usize) {
+        if self.values.len() < cap {
+            self.values.push(value);
+        } else if cap > 0 {
+            self.values[self.next] = value;
+            self.next = (self.next + 1) % cap;
+        }
+    }
+
+    /// Returns the upper Tukey fence, or `None` while warming up
+    fn fence(&self, cfg: &NewtonConfig) -> Option<f64> {
+        if self.values.len() < cfg.warmup.max(1) {
+            return None;
+        }
+        let mut sorted = self.values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        let (q1, q3) = (percentile(&sorted, 0.25), percentile(&sorted, 0.75));
+        Some(f64::max(q3 + cfg.fence * (q3 - q1), cfg.tolerance))
+    }
+}
+
+/// Tracks the peers of a [`Node`] by ID `K` and ignores samples from peers
+/// that violate the invariants, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Newton<K, V> {
+    cfg: NewtonConfig,
+    peers: BTreeMap<K, Peer<V>>,
+    // The sum of the vectors and magnitudes of the peers that are not flagged
+    sum: V,
+    magnitudes: f64,
+    honest: usize,
+    forces: History,
+    movements: History,
+}
+
+impl<K, V> Newton<K, V>
+where
+    K: Ord,
+    V: Vector + Clone,
+{
+    /// Creates a tracker that knows no peers
+    pub fn new(cfg: NewtonConfig) -> Self {
+        Self {
+            cfg,
+            peers: BTreeMap::new(),
+            sum: V::default(),
+            magnitudes: 0.0,
+            honest: 0,
+            forces: History {
+                values: Vec::new(),
+                next: 0,
+            },
+            movements: History {
+                values: Vec::new(),
+                next: 0,
+            },
+        }
+    }
+
+    /// Returns the configuration
+    pub fn config(&self) -> &NewtonConfig { &self.cfg }
+
+    /// Checks the sample from `peer` against the invariants and, if it passes,
+    /// updates `node` with [`Node::try_update`]
+    ///
+    /// A peer that fails a check is flagged and all of its samples are
+    /// ignored until it is [forgiven](Newton::forgive). A sample only counts
+    /// towards the invariants once `node` accepts it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `peer` is flagged or fails a check, for which
+    /// [`Error::is_rejected`] is true, or [`Node::try_update`] fails
+    pub fn update<A: Window>(
+        &mut self,
+        node: &mut Node<V, A>,
+        peer: K,
+        rtt: Duration,
+        other: &Coord<V>,
+    ) -> Result<()> {
+        let rejected = Error {
+            kind: ErrorKind::Rejected,
+        };
+        if self.violation(&peer).is_some() {
+            return Err(rejected);
+        }
+        node.coordinate().check_dimensions(other)?;
+
+        let prev = self.peers.get(&peer);
+        let force = node.movement_from(rtt, other);
+        let movement = prev.map(|p| p.vec.distance(&other.vec));
+        let magnitude = other.vec.magnitude();
+
+        // The sums with this report replacing the peer's last one
+        let mut sum = self.sum.clone() + other.vec.clone();
+        let mut magnitudes = self.magnitudes + magnitude;
+        let mut honest = self.honest + 1;
+        if let Some(p) = prev {
+            sum = sum.difference(&p.vec);
+            magnitudes -= p.magnitude;
+            honest -= 1;
+        }
+
+        let violation = if self.forces.fence(&self.cfg).map_or(false, |f| force > f) {
+            Some(Violation::Force)
+        } else if movement
+            .zip(self.movements.fence(&self.cfg))
+            .map_or(false, |(m, f)| m > f)
+        {
+            Some(Violation::Movement)
+        } else if honest >= self.cfg.min_peers.max(1) && {
+            let before = if self.honest == 0 {
+                0.0
+            } else {
+                self.sum.magnitude() / self.honest as f64
+            };
+            let after = sum.magnitude() / honest as f64;
+            after > before && after > self.cfg.centroid_max * magnitudes / honest as f64
+        } {
+            Some(Violation::Centroid)
+        } else {
+            None
+        };
+
+        if let Some(v) = violation {
+            if let Some(p) = prev {
+                self.sum = self.sum.difference(&p.vec);
+                self.magnitudes -= p.magnitude;
+                self.honest -= 1;
+            }
+            self.peers.insert(
+                peer,
+                Peer {
+                    vec: other.vec.clone(),
+                    magnitude,
+                    violation: Some(v),
+                },
+            );
+            return Err(rejected);
+        }
+
+        node.try_update(rtt, other)?;
+        self.sum = sum;
+        self.magnitudes = magnitudes;
+        self.honest = honest;
+        self.forces.push(force, self.cfg.history);
+        if let Some(m) = movement {
+            self.movements.push(m, self.cfg.history);
+        }
+        self.peers.insert(
+            peer,
+            Peer {
+                vec: other.vec.clone(),
+                magnitude,
+                violation: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Returns the invariant `peer` violated, if it is flagged
+    pub fn violation(&self, peer: &K) -> Option<Violation> {
+        self.peers.get(peer).and_then(|p| p.violation)
+    }
+
+    /// Returns the flagged peers along with the invariant each violated
+    pub fn flagged(&self) -> impl Iterator<Item = (&K, Violation)> {
+        self.peers
+            .iter()
+            .filter_map(|(k, p)| p.violation.map(|v| (k, v)))
+    }
+
+    /// Forgets everything about `peer`, so its next sample is checked as if
+    /// it were new
+    pub fn forgive(&mut self, peer: &K) {
+        if let Some(p) = self.peers.remove(peer) {
+            if p.violation.is_none() {
+                self.sum = self.sum.difference(&p.vec);
+                self.magnitudes -= p.magnitude;
+                self.honest -= 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{heapless::VecD, Policy};
+
+    const RTT: Duration = Duration::from_millis(10);
+
+    fn peers() -> Vec<Coord<VecD<2>>> {
+        (0..8)
+            .map(|i| {
+                let a = i as f64 * crate::std::f64::consts::PI / 4.0;
+                let mut c = Coord::from(VecD::from([0.01 * a.cos(), 0.01 * a.sin()]));
+                c.set_error_estimate(0.2);
+                c
+            })
+            .collect()
+    }
+
+    fn warm(cfg: NewtonConfig) -> (Node<VecD<2>>, Newton<usize, VecD<2>>) {
+        let mut node = Node::new();
+        let mut newton = Newton::new(cfg);
+        for _ in 0..10 {
+            for (id, peer) in peers().iter().enumerate() {
+                newton.update(&mut node, id, RTT, peer).unwrap();
+            }
+        }
+        assert_eq!(newton.flagged().count(), 0);
+        (node, newton)
+    }
+
+    #[test]
+    fn force() {
+        let (mut node, mut newton) = warm(NewtonConfig::default());
+        let mut liar = Coord::from(VecD::from([1.0, 0.0]));
+        liar.set_error_estimate(1.0e-6);
+        assert!(newton
+            .update(&mut node, 8, RTT, &liar)
+            .unwrap_err()
+            .is_rejected());
+        assert_eq!(newton.violation(&8), Some(Violation::Force));
+
+        // later honest looking samples are ignored too
+        assert!(newton
+            .update(&mut node, 8, RTT, &peers()[0])
+            .unwrap_err()
+            .is_rejected());
+
+        newton.forgive(&8);
+        assert_eq!(newton.violation(&8), None);
+        newton.update(&mut node, 8, RTT, &peers()[0]).unwrap();
+    }
+
+    #[test]
+    fn movement() {
+        let (mut node, mut newton) = warm(NewtonConfig {
+            fence: 100.0,
+            ..Default::default()
+        });
+        // peer 3 teleports while keeping a plausible error and RTT
+        let mut far = Coord::from(VecD::from([0.0, 0.05]));
+        far.set_error_estimate(1.5);
+        assert!(newton
+            .update(&mut node, 3, RTT, &far)
+            .unwrap_err()
+            .is_rejected());
+        assert_eq!(newton.violation(&3), Some(Violation::Movement));
+        assert_eq!(
+            newton.flagged().collect::<Vec<_>>(),
+            [(&3, Violation::Movement)]
+        );
+    }
+
+    #[test]
+    fn centroid() {
+        let (mut node, mut newton) = warm(NewtonConfig {
+            fence: 1.0e9,
+            centroid_max: 0.5,
+            ..Default::default()
+        });
+        // a new peer far off to one side drags the centroid with it
+        let mut far = Coord::from(VecD::from([0.2, 0.0]));
+        far.set_error_estimate(1.5);
+        assert!(newton
+            .update(&mut node, 8, RTT, &far)
+            .unwrap_err()
+            .is_rejected());
+        assert_eq!(newton.violation(&8), Some(Violation::Centroid));
+
+        // a small move toward the origin passes
+        let mut near = Coord::from(VecD::from([0.0095, 0.0]));
+        near.set_error_estimate(0.2);
+        newton.update(&mut node, 0, RTT, &near).unwrap();
+    }
+
+    #[test]
+    fn rejected_by_node() {
+        let (mut node, mut newton) = warm(NewtonConfig::default());
+        let forces = newton.forces.values.clone();
+        let movements = newton.movements.values.clone();
+        let sum = newton.sum;
+
+        // the samples pass the checks but the node's policy refuses them, so
+        // nothing is recorded
+        node.set_policy(Policy {
+            error_min: 0.5,
+            ..Default::default()
+        });
+        for (id, peer) in [(8, &peers()[0]), (3, &peers()[3])] {
+            assert!(newton
+                .update(&mut node, id, RTT, peer)
+                .unwrap_err()
+                .is_rejected());
+        }
+        assert_eq!(node.policy_stats().rejected, 2);
+        assert_eq!(newton.forces.values, forces);
+        assert_eq!(newton.movements.values, movements);
+        assert_eq!(newton.sum, sum);
+        assert!(!newton.peers.contains_key(&8));
+        assert_eq!(newton.flagged().count(), 0);
+    }
+}
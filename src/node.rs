@@ -1,7 +1,17 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
 use crate::{
     error::{Error, ErrorKind, Result},
     std::time::Duration,
-    Coord, Vector, DEFAULT_HEIGHT_MIN,
+    window::{Aggregate, Sample, Window},
+    Coord, Estimate, Vector, DEFAULT_HEIGHT_MIN,
+};
+#[cfg(feature = "alloc")]
+use crate::{
+    rank::{RankConfig, Ranked},
+    std::borrow::Borrow,
+    trust::TrustStore,
 };
 
 /// Tunables that affect how [`Node`]s handle coordinates and updates
@@ -11,7 +21,9 @@ pub struct Config {
     /// value of an error estimate before making any updates.
     pub error_max: f64,
 
-    /// The minimum value of the height parameter
+    /// The minimum value of the height parameter, `10e-6` seconds by default
+    /// as in Serf. Heights only change in proportion to their current value,
+    /// so this must be above `0.0` for nodes to learn them.
     pub height_min: f64,
 
     /// How hard gravity pulls coordinates back to center to avoid constant
@@ -23,6 +35,27 @@ pub struct Config {
 
     /// The maximum impact an observation can have on a node's coordinate
     pub cc: f64,
+
+    /// Only count overestimates (`max(dist - rtt, 0) / rtt`) as error when
+    /// updating the error estimate, as older releases did. By default
+    /// underestimates count too (`|dist - rtt| / rtt`), as in the Vivaldi
+    /// paper, otherwise a node consistently underestimating its latencies
+    /// looks more confident than it is.
+    pub legacy_error: bool,
+
+    /// Keep the mean residual of the adjustment window even when it's
+    /// negative, so adjustments can lower estimates as well as raise them.
+    /// [`Coord::distance_to`] still never estimates less than the distance
+    /// between the vectors. By default negative adjustments are ignored.
+    pub signed_adjustments: bool,
+
+    /// How the samples in the adjustment window are combined into the offset
+    pub adjustment_aggregate: Aggregate,
+
+    /// Adjustment samples older than this are dropped, as measured by the
+    /// time given to [`Node::set_now`]. By default samples only leave the
+    /// window when newer ones replace them.
+    pub adjustment_max_age: Option<Duration>,
 }
 
 impl Default for Config {
@@ -33,36 +66,171 @@ impl Default for Config {
             gravity_rho: 150.0,
             ce: 0.25,
             cc: 0.25,
+            legacy_error: false,
+            signed_adjustments: false,
+            adjustment_aggregate: Aggregate::Mean,
+            adjustment_max_age: None,
+        }
+    }
+}
+
+/// Checks a [`Node`] applies to each sample before trusting it
+///
+/// A peer can claim a tiny error estimate, or a far away vector, to drag a
+/// node across the space. Each check flags such samples, which are then
+/// rejected or, if `clamp` is set and the check allows it, applied with their
+/// impact reduced to the limit. The default policy trusts every sample.
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use violin::{heapless::VecD, Coord, Node, Policy};
+///
+/// let mut node = Node::<VecD<2>>::with_coord(VecD::from([0.01, 0.0]));
+/// node.set_policy(Policy {
+///     movement_max: 0.005,
+///     ..Default::default()
+/// });
+///
+/// let mut liar = Coord::from(VecD::from([100.0, 0.0]));
+/// liar.set_error_estimate(1.0e-6);
+/// assert!(!node.update(Duration::from_millis(10), &liar));
+/// assert_eq!(node.coordinate().raw_coord(), &VecD::from([0.01, 0.0]));
+/// assert_eq!(node.policy_stats().rejected, 1);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Policy {
+    /// Samples from peers claiming an error estimate below this are flagged.
+    /// When clamping, the peer's error estimate is raised to this value.
+    pub error_min: f64,
+
+    /// Samples that would move the coordinate further than this (the distance
+    /// moved by the vector plus the change in height, in seconds) are flagged.
+    /// When clamping, the sample's weight is reduced so the coordinate moves
+    /// exactly this far.
+    pub movement_max: f64,
+
+    /// Samples whose relative error (`|estimate - rtt| / rtt`) is more than
+    /// this many times the larger of the node's and the peer's error
+    /// estimates are flagged, and always rejected.
+    ///
+    /// Genuine route changes look the same as lies to this check, so keep it
+    /// generous.
+    pub residual_max: f64,
+
+    /// The number of samples a node applies before checking
+    /// [`Policy::residual_max`]. Fresh coordinates start out with a tiny
+    /// error estimate, which would otherwise reject every sample and keep it
+    /// from ever growing.
+    pub residual_warmup: u64,
+
+    /// Clamp samples flagged by `error_min` or `movement_max` instead of
+    /// rejecting them
+    pub clamp: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            error_min: 0.0,
+            movement_max: f64::INFINITY,
+            residual_max: f64::INFINITY,
+            residual_warmup: 16,
+            clamp: false,
         }
     }
 }
 
+/// Counts of the samples a [`Node`]'s [`Policy`] flagged, see
+/// [`Node::policy_stats`]
+///
+/// A sample may be flagged by more than one check.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct PolicyStats {
+    /// Samples from peers claiming an error estimate below
+    /// [`Policy::error_min`]
+    pub low_error: u64,
+    /// Samples that would have moved the coordinate more than
+    /// [`Policy::movement_max`]
+    pub movement: u64,
+    /// Samples with a residual beyond [`Policy::residual_max`]
+    pub residual: u64,
+    /// Samples that were not applied at all
+    pub rejected: u64,
+    /// Samples that were applied with a reduced impact
+    pub clamped: u64,
+}
+
+/// How a [`Node`] picks the direction to move in when its coordinate overlaps
+/// the peer's
+///
+/// With no direction between them, Vivaldi pushes the two nodes apart along a
+/// random one. This matters most when starting out as every node begins at the
+/// origin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Overlap {
+    /// Move along a random unit vector drawn from the node's generator, see
+    /// [`Node::seed`]
+    Random,
+    /// Always move along the first dimension. This is deterministic, which
+    /// can be useful in tests, but keeps nodes starting from the origin on a
+    /// single line.
+    FirstDimension,
+}
+
+impl Default for Overlap {
+    fn default() -> Self { Self::Random }
+}
+
 /// A `Node` is a higher level construct that abstracts over *using* Vivaldi and
 /// includes things like maintaining adjustment calculations over a window of
 /// RTT measurements.
 ///
 /// The two generic arguments `V` and `A` are the coordinate vector `V` and the
-/// adjustment window buffer `A`. By default the adjustment window buffer is set
-/// to a heapless 0 sized buffer (`[0f64; 0]`) but can be made to be as large as
-/// one requires, and use either the `heapless::VecD` or `heap::VecD` (with the
-/// `alloc` feature)
-#[derive(Debug, Clone, Default)]
-pub struct Node<V, A = crate::heapless::VecD<0>> {
+/// adjustment [`Window`] `A`. By default the adjustment window is a heapless
+/// window of capacity 0, which disables adjustments, but can be made to be as
+/// large as one requires, and use either the `heapless::Window` or
+/// `heap::Window` (with the `alloc` feature)
+///
+/// Each node has its own generator for [`Overlap::Random`]. The constructors
+/// that aren't given an `rng` quietly seed it from the thread's random number
+/// generator when the `std` and `rand` features are enabled, so runs can't be
+/// reproduced. Without them every such node starts from seed `0` and picks the
+/// same directions as every other node. To drive a system from one seed, create
+/// nodes with [`Node::with_cfg_and_rng`] or [`Node::rand_with_cfg_and_rng`], or
+/// give each its own seed with [`Node::seed`].
+#[derive(Debug, Clone)]
+pub struct Node<V, A = crate::heapless::Window<0>> {
     coord: Coord<V>,
     cfg: Config,
     adjustments: A,
-    adj_idx: usize,
+    now: Duration,
+    policy: Policy,
+    policy_stats: PolicyStats,
+    applied: u64,
+    overlap: Overlap,
+    rng: u64,
+}
+
+impl<V, A> Default for Node<V, A>
+where
+    V: Vector,
+    A: Window,
+{
+    fn default() -> Self { Self::with_coord_and_cfg(Coord::default(), Config::default()) }
 }
 
 impl<V, A> Node<V, A>
 where
     V: Vector,
-    A: Vector,
+    A: Window,
 {
-    /// Create a new node with a default coordinate and configuration
+    /// Create a new node with a default coordinate and configuration, seeded
+    /// as described on [`Node`]
     pub fn new() -> Self { Self::default() }
 
-    /// Create a new node with a default coordinate
+    /// Create a new node with a default coordinate, seeded as described on
+    /// [`Node`]
     pub fn with_config(cfg: Config) -> Self { Self::with_coord_and_cfg(Coord::default(), cfg) }
 
     /// The coordinate's error_estimate and height will be reset using the
@@ -80,6 +248,13 @@ where
     /// value. Likewise, the error_estimate will be lowered to the specified
     /// config's error_max if it is beyond that value.
     pub fn with_coord_and_cfg<U>(coord: U, cfg: Config) -> Self
+    where
+        U: Into<Coord<V>>,
+    {
+        Self::with_coord_cfg_and_seed(coord, cfg, default_seed())
+    }
+
+    fn with_coord_cfg_and_seed<U>(coord: U, cfg: Config, seed: u64) -> Self
     where
         U: Into<Coord<V>>,
     {
@@ -90,23 +265,99 @@ where
             coord,
             cfg,
             adjustments: A::default(),
-            adj_idx: 0,
+            now: Duration::ZERO,
+            policy: Policy::default(),
+            policy_stats: PolicyStats::default(),
+            applied: 0,
+            overlap: Overlap::default(),
+            rng: seed,
         }
     }
 
-    /// Create a new node with an initialized random coordinate
+    /// Create a new node with a default coordinate, seeding its generator
+    /// from `rng` rather than the thread's (see [`Node`])
+    #[cfg(feature = "rand")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rand")))]
+    pub fn with_cfg_and_rng<R: rand::Rng + ?Sized>(cfg: Config, rng: &mut R) -> Self {
+        Self::with_coord_cfg_and_seed(Coord::default(), cfg, rng.gen())
+    }
+
+    /// Create a new node with an initialized random coordinate, drawing both
+    /// the coordinate and the seed from the thread's random number generator
     #[cfg(all(feature = "std", feature = "rand"))]
     #[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "rand"))))]
     pub fn rand() -> Self { Self::rand_with_cfg(Config::default()) }
 
-    /// Create a new node with an initialized random coordinate
+    /// Create a new node with an initialized random coordinate, drawing both
+    /// the coordinate and the seed from the thread's random number generator
     #[cfg(all(feature = "std", feature = "rand"))]
     #[cfg_attr(docsrs, doc(cfg(all(feature = "std", feature = "rand"))))]
-    pub fn rand_with_cfg(cfg: Config) -> Self { Self::with_coord_and_cfg(Coord::rand(), cfg) }
+    pub fn rand_with_cfg(cfg: Config) -> Self {
+        Self::rand_with_cfg_and_rng(cfg, &mut rand::thread_rng())
+    }
+
+    /// Create a new node with a random coordinate and seed drawn from `rng`
+    /// (see [`Coord::rand_with_rng`])
+    #[cfg(feature = "rand")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rand")))]
+    pub fn rand_with_rng<R: rand::Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::rand_with_cfg_and_rng(Config::default(), rng)
+    }
+
+    /// Create a new node with a random coordinate and seed drawn from `rng`
+    /// (see [`Coord::rand_with_rng`])
+    #[cfg(feature = "rand")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "rand")))]
+    pub fn rand_with_cfg_and_rng<R: rand::Rng + ?Sized>(cfg: Config, rng: &mut R) -> Self {
+        let coord = Coord::rand_with_rng(rng);
+        Self::with_coord_cfg_and_seed(coord, cfg, rng.gen())
+    }
 
     /// Returns the inner coordinate
     pub fn coordinate(&self) -> &Coord<V> { &self.coord }
 
+    /// Returns the policy applied to each sample
+    pub fn policy(&self) -> Policy { self.policy }
+
+    /// Sets the policy applied to each sample from now on
+    pub fn set_policy(&mut self, policy: Policy) { self.policy = policy; }
+
+    /// Returns the counts of samples flagged by the policy
+    pub fn policy_stats(&self) -> PolicyStats { self.policy_stats }
+
+    /// Returns the window of adjustment samples
+    pub fn adjustments(&self) -> &A { &self.adjustments }
+
+    /// Sets the current time, which timestamps adjustment samples from now on
+    /// and drops those older than [`Config::adjustment_max_age`]. Time can be
+    /// measured from any epoch as long as it doesn't go backwards, and stays
+    /// at zero unless set.
+    pub fn set_now(&mut self, now: Duration) {
+        self.now = now;
+        if self.cfg.adjustment_max_age.is_some() {
+            self.apply_adjustments();
+        }
+    }
+
+    /// Returns how the direction is picked when coordinates overlap
+    pub fn overlap(&self) -> Overlap { self.overlap }
+
+    /// Sets how the direction is picked when coordinates overlap
+    pub fn set_overlap(&mut self, overlap: Overlap) { self.overlap = overlap; }
+
+    /// Reseeds the generator used for [`Overlap::Random`], making the
+    /// directions picked from now on reproducible
+    pub fn seed(&mut self, seed: u64) { self.rng = seed; }
+
+    /// Returns how far an unweighted update from `other` would move the
+    /// coordinate, ignoring the policy
+    #[cfg(feature = "alloc")]
+    pub(crate) fn movement_from(&self, rtt: Duration, other: &Coord<V>) -> f64 {
+        let rtt = f64::max(f64::MIN_POSITIVE, rtt.as_secs_f64());
+        self.coord
+            .movement(rtt, other, other.error_estimate, 1.0, &self.cfg)
+    }
+
     /// Sets the inner coordinate, however the coordinate's error_estimate and
     /// height will be set within the bounds of the current node
     /// configuration.
@@ -125,6 +376,49 @@ where
         Duration::from_secs_f64(self.coord.distance_to(other))
     }
 
+    /// Returns estimated latency to `other` along with bounds on the true
+    /// latency. See [`Coord::estimate_to`] for how the bounds are derived and
+    /// what `k` controls, including that a negative or NaN `k` gives bounds
+    /// equal to the estimate.
+    pub fn estimate_to(&self, other: &Coord<V>, k: f64) -> Estimate<Duration> {
+        let est = self.coord.estimate_to(other, k);
+        Estimate {
+            rtt: Duration::from_secs_f64(est.rtt),
+            low: Duration::from_secs_f64(est.low),
+            high: if est.high.is_finite() {
+                Duration::from_secs_f64(est.high)
+            } else {
+                Duration::MAX
+            },
+        }
+    }
+
+    /// Returns the `k` candidates with the lowest estimated latency from this
+    /// node, closest first. See [`rank`](crate::rank) for how ties and
+    /// uncertain candidates are handled.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn rank<I, C>(
+        &self,
+        candidates: impl IntoIterator<Item = (I, C)>,
+        k: usize,
+        cfg: &RankConfig,
+    ) -> Vec<Ranked<I, Duration>>
+    where
+        C: Borrow<Coord<V>>,
+    {
+        self.coord
+            .rank(candidates, k, cfg)
+            .into_iter()
+            .map(|r| Ranked {
+                id: r.id,
+                rtt: Duration::from_secs_f64(r.rtt),
+                error_estimate: r.error_estimate,
+                score: r.score,
+            })
+            .collect()
+    }
+
     /// Returns the raw error estimate.
     pub fn error_estimate(&self) -> f64 { self.coord.error_estimate() }
 
@@ -276,14 +570,64 @@ where
     ///
     /// # Errors
     ///
-    /// Returns an error if update caused the coordinate to become invalid
+    /// Returns an error if the coordinates have different dimensions, the
+    /// sample was rejected by the node's [`Policy`] or the update caused the
+    /// coordinate to become invalid
     pub fn try_update(&mut self, rtt: Duration, other: &Coord<V>) -> Result<()> {
+        self.try_update_weighted(rtt, other, 1.0)
+    }
+
+    /// Update the node's coordinate based off the RTT of the `other`
+    /// coordinate, scaling the impact of the sample on both the coordinate
+    /// and error estimate by `weight`.
+    ///
+    /// A `weight` of `1.0` is the same as [`Node::try_update`] while `0.0`
+    /// skips the update entirely. This can be used to down-weight samples
+    /// from suspicious links such as those flagged by
+    /// [`tiv::TivReport`](crate::tiv::TivReport).
+    ///
+    /// # Panics
+    ///
+    /// Panics if any:
+    ///
+    /// - This coordinate's AND the remote's error estimate `<= 0.0`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `weight` is not within `[0.0, 1.0]`, the coordinates
+    /// have different dimensions, the sample was rejected by the node's
+    /// [`Policy`] or the update caused the coordinate to become invalid
+    pub fn try_update_weighted(
+        &mut self,
+        rtt: Duration,
+        other: &Coord<V>,
+        weight: f64,
+    ) -> Result<()> {
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(Error {
+                kind: ErrorKind::InvalidWeight,
+            });
+        }
+        self.coord.check_dimensions(other)?;
+        if weight == 0.0 {
+            return Ok(());
+        }
+
         let rtt = f64::max(f64::MIN_POSITIVE, rtt.as_secs_f64());
+        let (other_error, weight) = self.apply_policy(rtt, other, weight)?;
 
-        self.coord.update(rtt, other, &self.cfg);
+        let state = &mut self.rng;
+        let mut uniform = || uniform(state);
+        let uniform: Option<&mut dyn FnMut() -> f64> = match self.overlap {
+            Overlap::Random => Some(&mut uniform),
+            Overlap::FirstDimension => None,
+        };
+        self.coord
+            .update_as(rtt, other, other_error, weight, &self.cfg, uniform);
         self.update_offset(rtt, other);
 
         if self.coord.is_finite() {
+            self.applied += 1;
             return Ok(());
         }
         Err(Error {
@@ -291,47 +635,499 @@ where
         })
     }
 
+    /// Update the node's coordinate based off the RTT of the `other`
+    /// coordinate, scaling the impact of the sample by the trust weight of
+    /// `peer` in `trust` (see [`Node::try_update_weighted`])
+    ///
+    /// # Panics
+    ///
+    /// Panics if any:
+    ///
+    /// - This coordinate's AND the remote's error estimate `<= 0.0`
+    ///
+    /// # Errors
+    ///
+    /// The same as [`Node::try_update_weighted`]
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn try_update_trusted<K: Ord>(
+        &mut self,
+        trust: &TrustStore<K>,
+        peer: &K,
+        rtt: Duration,
+        other: &Coord<V>,
+    ) -> Result<()> {
+        self.try_update_weighted(rtt, other, trust.get(peer))
+    }
+
     /// Gravity pulls the coordinate back toward the origin to prevent drift
     pub fn update_gravity(&mut self, origin: &Coord<V>) {
         self.coord.apply_gravity(origin, &self.cfg);
     }
 
+    /// Returns the error estimate and weight to use for the sample, or an error
+    /// if the sample is rejected
+    fn apply_policy(&mut self, rtt: f64, other: &Coord<V>, weight: f64) -> Result<(f64, f64)> {
+        let p = self.policy;
+        let stats = &mut self.policy_stats;
+        let reject = |stats: &mut PolicyStats| {
+            stats.rejected += 1;
+            Err(Error {
+                kind: ErrorKind::Rejected,
+            })
+        };
+
+        let residual = (self.coord.distance_to(other) - rtt) / rtt;
+        let error = f64::max(self.coord.error_estimate, other.error_estimate);
+        if self.applied >= p.residual_warmup && crate::abs(residual) > p.residual_max * error {
+            stats.residual += 1;
+            return reject(stats);
+        }
+
+        let mut other_error = other.error_estimate;
+        let mut clamped = false;
+        if other_error < p.error_min {
+            stats.low_error += 1;
+            if !p.clamp {
+                return reject(stats);
+            }
+            other_error = p.error_min;
+            clamped = true;
+        }
+
+        let mut weight = weight;
+        let movement = self
+            .coord
+            .movement(rtt, other, other_error, weight, &self.cfg);
+        if movement > p.movement_max {
+            stats.movement += 1;
+            if !p.clamp {
+                return reject(stats);
+            }
+            weight *= p.movement_max / movement;
+            clamped = true;
+        }
+
+        if clamped {
+            stats.clamped += 1;
+        }
+        Ok((other_error, weight))
+    }
+
     fn update_offset(&mut self, rtt: f64, other: &Coord<V>) {
-        if A::LEN == 0 {
+        if self.adjustments.capacity() == 0 {
             return;
         }
 
-        self.adjustments.as_mut()[self.adj_idx] = rtt - self.coord.raw_distance_to(other);
-        self.adj_idx = (self.adj_idx + 1) % A::LEN;
+        self.adjustments.push(Sample {
+            residual: rtt - self.coord.raw_distance_to(other),
+            at: self.now,
+        });
+        self.apply_adjustments();
+    }
+
+    /// Expires old adjustment samples and sets the offset from those left
+    fn apply_adjustments(&mut self) {
+        if let Some(max_age) = self.cfg.adjustment_max_age {
+            self.adjustments.expire(self.now.saturating_sub(max_age));
+        }
 
-        let adj_sum = self.adjustments.as_ref().iter().fold(0.0, |acc, n| acc + n);
-        self.coord.offset = adj_sum / (2 * A::LEN) as f64;
+        // Each side of a link makes up half of the adjustment
+        let offset = self.adjustments.aggregate(self.cfg.adjustment_aggregate) / 2.0;
+        if self.cfg.signed_adjustments {
+            self.coord.set_signed_offset(offset);
+        } else {
+            self.coord.set_offset(offset);
+        }
     }
 }
 
 impl<V, A> Node<V, A>
 where
     V: Vector + Clone,
-    A: Vector,
+    A: Window,
 {
     /// Update the node's coordinate based off the RTT of the `other`
     /// coordinate. If the update causes the coordinate to become invalid,
-    /// it will be reset and return `false`
+    /// it will be reset and return `false`. Samples rejected by the node's
+    /// [`Policy`] also return `false`, leaving the coordinate unchanged.
     ///
     /// # Panics
     ///
     /// Panics if any:
     ///
     /// - This coordinate's OR the remote's error estimate `<= 0.0`
+    /// - The coordinates have different dimensions
     pub fn update(&mut self, rtt: Duration, other: &Coord<V>) -> bool {
+        self.update_weighted(rtt, other, 1.0)
+    }
+
+    /// Update the node's coordinate based off the RTT of the `other`
+    /// coordinate, scaling the impact of the sample by `weight` (see
+    /// [`Node::try_update_weighted`]). If the update causes the coordinate to
+    /// become invalid, it will be reset and return `false`. Samples rejected
+    /// by the node's [`Policy`] also return `false`, leaving the coordinate
+    /// unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any:
+    ///
+    /// - This coordinate's OR the remote's error estimate `<= 0.0`
+    /// - `weight` is not within `[0.0, 1.0]`
+    /// - The coordinates have different dimensions
+    pub fn update_weighted(&mut self, rtt: Duration, other: &Coord<V>, weight: f64) -> bool {
+        assert!((0.0..=1.0).contains(&weight));
+        assert!(
+            self.coord.check_dimensions(other).is_ok(),
+            "dimension mismatch"
+        );
         let coord = self.coord.clone();
-        if let Err(Error {
-            kind: ErrorKind::InvalidCoordinate,
-        }) = self.try_update(rtt, other)
-        {
-            self.coord = coord;
-            return false;
+        match self.try_update_weighted(rtt, other, weight) {
+            Ok(()) => true,
+            Err(Error {
+                kind: ErrorKind::InvalidCoordinate,
+            }) => {
+                self.coord = coord;
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Update the node's coordinate based off the RTT of the `other`
+    /// coordinate, scaling the impact of the sample by the trust weight of
+    /// `peer` in `trust` (see [`Node::update_weighted`])
+    ///
+    /// # Panics
+    ///
+    /// The same as [`Node::update_weighted`]
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    pub fn update_trusted<K: Ord>(
+        &mut self,
+        trust: &TrustStore<K>,
+        peer: &K,
+        rtt: Duration,
+        other: &Coord<V>,
+    ) -> bool {
+        self.update_weighted(rtt, other, trust.get(peer))
+    }
+}
+
+#[cfg(all(feature = "std", feature = "rand"))]
+fn default_seed() -> u64 { rand::random() }
+
+#[cfg(not(all(feature = "std", feature = "rand")))]
+fn default_seed() -> u64 { 0 }
+
+/// Advances the SplitMix64 `state` and returns a value within `[-1.0, 1.0)`
+fn uniform(state: &mut u64) -> f64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 52) as f64 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{close, heapless::VecD};
+
+    fn node() -> Node<VecD<2>> {
+        let mut coord = Coord::from(VecD::from([0.01, 0.0]));
+        coord.set_error_estimate(0.5);
+        Node::with_coord(coord)
+    }
+
+    fn peer(x: f64, error_estimate: f64) -> Coord<VecD<2>> {
+        let mut c = Coord::from(VecD::from([x, 0.0]));
+        c.set_error_estimate(error_estimate);
+        c
+    }
+
+    const RTT: Duration = Duration::from_millis(20);
+
+    #[cfg_attr(feature = "rand", test)]
+    #[cfg(feature = "rand")]
+    fn seeded_rand() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let cfg = Config {
+            height_min: 0.01,
+            ..Default::default()
+        };
+        let a = Node::<VecD<4>>::rand_with_cfg_and_rng(cfg, &mut StdRng::seed_from_u64(1));
+        let b = Node::<VecD<4>>::rand_with_cfg_and_rng(cfg, &mut StdRng::seed_from_u64(1));
+        let c = Node::<VecD<4>>::rand_with_rng(&mut StdRng::seed_from_u64(2));
+        assert_eq!(a.coordinate().raw_coord(), b.coordinate().raw_coord());
+        assert_ne!(a.coordinate().raw_coord(), c.coordinate().raw_coord());
+        assert_eq!(a.coordinate().height(), 0.01);
+        assert!(a
+            .coordinate()
+            .raw_coord()
+            .as_ref()
+            .iter()
+            .all(|n| (-1.0..1.0).contains(n)));
+    }
+
+    #[test]
+    fn default_policy_trusts_everything() {
+        let mut n = node();
+        assert!(n.update(RTT, &peer(50.0, 1.0e-9)));
+        assert_eq!(n.policy_stats(), PolicyStats::default());
+    }
+
+    #[test]
+    fn low_error() {
+        let policy = Policy {
+            error_min: 0.1,
+            ..Default::default()
+        };
+        let mut n = node();
+        n.set_policy(policy);
+        assert!(n
+            .try_update(RTT, &peer(0.0, 0.01))
+            .unwrap_err()
+            .is_rejected());
+        assert_eq!(n.coordinate().raw_coord(), &VecD::from([0.01, 0.0]));
+
+        // clamping is the same as the peer reporting the minimum
+        let mut clamped = node();
+        clamped.set_policy(Policy {
+            clamp: true,
+            ..policy
+        });
+        clamped.try_update(RTT, &peer(0.0, 0.01)).unwrap();
+        let mut honest = node();
+        honest.try_update(RTT, &peer(0.0, 0.1)).unwrap();
+        assert_eq!(
+            clamped.coordinate().raw_coord(),
+            honest.coordinate().raw_coord()
+        );
+
+        assert_eq!(n.policy_stats().low_error, 1);
+        assert_eq!(n.policy_stats().rejected, 1);
+        assert_eq!(clamped.policy_stats().low_error, 1);
+        assert_eq!(clamped.policy_stats().clamped, 1);
+        assert_eq!(clamped.policy_stats().rejected, 0);
+    }
+
+    #[test]
+    fn movement() {
+        let policy = Policy {
+            movement_max: 0.001,
+            ..Default::default()
+        };
+        let mut n = node();
+        n.set_policy(policy);
+        let far = peer(10.0, 0.5);
+        assert!(!n.update(RTT, &far));
+        assert_eq!(n.policy_stats().movement, 1);
+
+        n.set_policy(Policy {
+            clamp: true,
+            ..policy
+        });
+        assert!(n.update(RTT, &far));
+        let moved = n
+            .coordinate()
+            .raw_coord()
+            .distance(&VecD::from([0.01, 0.0]));
+        assert!((moved - 0.001).abs() < 1.0e-5);
+        assert_eq!(n.policy_stats().clamped, 1);
+
+        // small movements pass untouched
+        let mut m = node();
+        m.set_policy(policy);
+        assert!(m.update(RTT, &peer(-0.005, 0.5)));
+        assert_eq!(m.policy_stats().movement, 0);
+    }
+
+    #[test]
+    fn residual() {
+        let mut n = node();
+        n.set_policy(Policy {
+            residual_max: 2.0,
+            residual_warmup: 0,
+            clamp: true,
+            ..Default::default()
+        });
+        // estimate 10s for a 20ms RTT is far beyond 2x the error estimate
+        assert!(n
+            .try_update(RTT, &peer(10.0, 0.5))
+            .unwrap_err()
+            .is_rejected());
+        // 15ms vs 20ms is a residual of 0.25
+        n.try_update(RTT, &peer(-0.005, 0.5)).unwrap();
+
+        let stats = n.policy_stats();
+        assert_eq!(stats.residual, 1);
+        assert_eq!(stats.rejected, 1);
+    }
+
+    #[test]
+    fn estimate_invalid_k() {
+        let mut n = node();
+        n.set_error_estimate(1.0);
+        let other = peer(0.05, 1.0);
+        // would have been a negative or NaN Duration and panicked
+        for k in [-5.0, f64::NAN] {
+            let est = n.estimate_to(&other, k);
+            assert_eq!(est.low, est.rtt);
+            assert_eq!(est.high, est.rtt);
+        }
+    }
+
+    #[test]
+    fn residual_from_new() {
+        let mut n = Node::<VecD<2>>::new();
+        n.set_overlap(Overlap::FirstDimension);
+        n.set_policy(Policy {
+            residual_max: 100.0,
+            ..Default::default()
+        });
+        let peer = Coord::from(VecD::from([0.05, 0.0]));
+
+        // a fresh node's tiny error estimate doesn't lock it out
+        for _ in 0..100 {
+            assert!(n.update(Duration::from_millis(60), &peer));
+        }
+        assert_eq!(n.policy_stats(), PolicyStats::default());
+        let rtt = n.distance_to(&peer).as_secs_f64();
+        assert!((0.055..0.065).contains(&rtt));
+
+        // once warmed up, wild samples are still rejected
+        assert!(!n.update(
+            Duration::from_millis(60),
+            &Coord::from(VecD::from([500.0, 0.0]))
+        ));
+        assert_eq!(n.policy_stats().residual, 1);
+    }
+
+    #[test]
+    fn signed_adjustments() {
+        // a fixed coordinate so only the adjustments change
+        let cfg = Config {
+            cc: 0.0,
+            height_min: 0.02,
+            ..Default::default()
+        };
+        let mut other = Coord::new();
+        other.set_height(0.02);
+        let overestimated = |cfg| {
+            let mut n = Node::<VecD<2>, crate::heapless::Window<4>>::with_coord_and_cfg(
+                VecD::from([0.1, 0.0]),
+                cfg,
+            );
+            for _ in 0..4 {
+                n.update(Duration::from_millis(100), &other);
+            }
+            n
+        };
+
+        // the default ignores the negative mean residual
+        let n = overestimated(cfg);
+        assert_eq!(n.coordinate().offset(), 0.0);
+        assert!(close(n.coordinate().distance_to(&other), 0.14));
+
+        let n = overestimated(Config {
+            signed_adjustments: true,
+            ..cfg
+        });
+        assert!(close(n.coordinate().offset(), -0.02));
+        assert!(close(n.coordinate().distance_to(&other), 0.12));
+    }
+
+    #[test]
+    fn adjustment_window() {
+        use crate::heapless::Window;
+
+        let cfg = Config {
+            cc: 0.0,
+            height_min: 0.0,
+            ..Default::default()
+        };
+        let other = Coord::new();
+        let rtts = [100, 100, 100, 900];
+        let adjusted = |cfg| {
+            let mut n = Node::<VecD<2>, Window<8>>::with_coord_and_cfg(VecD::from([0.1, 0.0]), cfg);
+            for (i, rtt) in rtts.iter().enumerate() {
+                n.set_now(Duration::from_secs(i as u64));
+                n.update(Duration::from_millis(*rtt), &other);
+            }
+            n
+        };
+
+        // the mean over the whole window, as a plain array of residuals did
+        let n = adjusted(cfg);
+        assert_eq!(n.adjustments().len(), 4);
+        assert_eq!(n.coordinate().offset(), 0.8 / 16.0);
+
+        // the median ignores the slow probe
+        let mut n = adjusted(Config {
+            adjustment_aggregate: Aggregate::Median,
+            adjustment_max_age: Some(Duration::from_secs(2)),
+            ..cfg
+        });
+        assert_eq!(n.coordinate().offset(), 0.0);
+        n.set_now(Duration::from_secs(5));
+        assert_eq!(n.adjustments().len(), 1);
+        assert_eq!(n.coordinate().offset(), 0.4);
+    }
+
+    #[cfg_attr(feature = "rand", test)]
+    #[cfg(feature = "rand")]
+    fn seeded_with_cfg() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let origin = Coord::<VecD<3>>::default();
+        let overlapped = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut n = Node::<VecD<3>>::with_cfg_and_rng(Config::default(), &mut rng);
+            assert_eq!(n.coordinate().raw_coord(), origin.raw_coord());
+            assert!(n.update(RTT, &origin));
+            *n.coordinate().raw_coord()
+        };
+        assert_eq!(overlapped(1), overlapped(1));
+        assert_ne!(overlapped(1), overlapped(2));
+    }
+
+    #[test]
+    fn overlap() {
+        let origin = Coord::<VecD<3>>::default();
+        let overlapped = |overlap, seed| {
+            let mut n = Node::<VecD<3>>::new();
+            n.set_overlap(overlap);
+            n.seed(seed);
+            assert!(n.update(RTT, &origin));
+            *n.coordinate().raw_coord()
+        };
+
+        let first = overlapped(Overlap::FirstDimension, 1);
+        assert!(first.as_ref()[0] > 0.0);
+        assert_eq!(&first.as_ref()[1..], [0.0, 0.0]);
+        assert_eq!(first, overlapped(Overlap::FirstDimension, 2));
+
+        let a = overlapped(Overlap::Random, 1);
+        assert_eq!(a, overlapped(Overlap::Random, 1));
+        assert_ne!(a, overlapped(Overlap::Random, 2));
+        assert!(a.as_ref().iter().all(|n| *n != 0.0));
+        assert!(close(a.magnitude(), first.magnitude()));
+    }
+
+    #[test]
+    fn uniform_range() {
+        let mut state = 0;
+        let (mut min, mut max) = (1.0, -1.0);
+        for _ in 0..1000 {
+            let n = uniform(&mut state);
+            assert!((-1.0..1.0).contains(&n));
+            min = f64::min(min, n);
+            max = f64::max(max, n);
         }
-        true
+        assert!(min < -0.9 && max > 0.9);
     }
 }
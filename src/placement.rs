@@ -0,0 +1,373 @@
+//! Choosing replica sites to minimize estimated client latency
+//!
+//! Given a set of candidate sites and the clients that will use them, [`place`]
+//! picks `k` sites so that the estimated RTT from each client to its nearest
+//! replica is minimized, either on average ([`Objective::Mean`], the k-median
+//! problem) or in the worst case ([`Objective::Max`], the k-center problem).
+//!
+//! Both problems are NP-hard, so [`place`] uses heuristics: a greedy
+//! construction adding the most beneficial site one at a time, optionally
+//! followed by a local search swapping chosen sites for unchosen ones while
+//! that improves the objective.
+//!
+//! ```rust
+//! use violin::{
+//!     heapless::VecD,
+//!     placement::{place, Objective, Strategy},
+//!     Coord,
+//! };
+//!
+//! let sites = [
+//!     Coord::from(VecD::from([0.0, 0.0])),
+//!     Coord::from(VecD::from([0.05, 0.05])),
+//!     Coord::from(VecD::from([0.1, 0.0])),
+//! ];
+//! let clients = [
+//!     Coord::from(VecD::from([0.0, 0.01])),
+//!     Coord::from(VecD::from([0.1, 0.01])),
+//! ];
+//!
+//! let p = place(&sites, &clients, None, 2, Objective::Mean, Strategy::Greedy).unwrap();
+//! let mut chosen = p.sites.clone();
+//! chosen.sort();
+//! assert_eq!(chosen, vec![0, 2]);
+//! assert!((p.objective - 0.01).abs() < 1.0e-6);
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    center::{total_weight, weight},
+    error::{Error, ErrorKind, Result},
+    Coord, Vector,
+};
+
+/// What [`place`] minimizes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Objective {
+    /// The (weighted) mean estimated RTT from each client to its nearest
+    /// replica
+    Mean,
+    /// The largest estimated RTT from any client to its nearest replica.
+    /// Weights do not scale the RTTs, but clients with a weight of `0.0` are
+    /// ignored.
+    Max,
+}
+
+/// How [`place`] searches for sites
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Strategy {
+    /// Add the site that most improves the objective, `k` times
+    Greedy,
+    /// Start from the greedy solution, then repeatedly make the single swap of
+    /// a chosen site for an unchosen one that most improves the objective,
+    /// for at most `max_swaps` swaps
+    LocalSearch {
+        /// The maximum number of swaps to make
+        max_swaps: usize,
+    },
+}
+
+/// The result of [`place`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    /// The indices of the chosen sites
+    pub sites: Vec<usize>,
+    /// The index of the site nearest to each client
+    pub assignments: Vec<usize>,
+    /// The objective value of the chosen sites
+    pub objective: f64,
+}
+
+/// Picks `k` of `sites` minimizing the estimated RTT from `clients` to their
+/// nearest chosen site
+///
+/// Distances are [`Coord::distance_to`] from each client to each site. If
+/// `weights` is `None` every client has a weight of `1.0`. This precomputes
+/// the estimated RTT from every client to every site, so requires `O(N·M)`
+/// memory.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - `k == 0` or `k > sites.len()`
+/// - `clients` is empty, or `weights` is not the same length as `clients`
+/// - A weight is negative, NaN or Infinite, or all weights are `0.0`
+pub fn place<T: Vector>(
+    sites: &[Coord<T>],
+    clients: &[Coord<T>],
+    weights: Option<&[f64]>,
+    k: usize,
+    objective: Objective,
+    strategy: Strategy,
+) -> Result<Placement> {
+    let total = total_weight(clients, weights)?;
+    let n = sites.len();
+    if k == 0 || k > n {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+
+    // dist[c * n + s] is the estimated RTT from client c to site s
+    let mut dist = vec![0.0; clients.len() * n];
+    for (c, client) in clients.iter().enumerate() {
+        for (s, site) in sites.iter().enumerate() {
+            dist[c * n + s] = client.distance_to(site);
+        }
+    }
+    let eval = Eval {
+        dist: &dist,
+        n,
+        weights,
+        total,
+        objective,
+    };
+
+    // Greedy construction, tracking each client's nearest chosen site
+    let mut chosen = Vec::with_capacity(k);
+    let mut nearest = vec![f64::INFINITY; clients.len()];
+    while chosen.len() < k {
+        let mut best = None;
+        let mut best_score = f64::INFINITY;
+        for s in (0..n).filter(|s| !chosen.contains(s)) {
+            let score =
+                eval.score((0..clients.len()).map(|c| f64::min(nearest[c], dist[c * n + s])));
+            if score < best_score || best.is_none() {
+                best = Some(s);
+                best_score = score;
+            }
+        }
+        // There are always unchosen sites since `k <= n`
+        let s = best.unwrap();
+        for (c, d) in nearest.iter_mut().enumerate() {
+            *d = f64::min(*d, dist[c * n + s]);
+        }
+        chosen.push(s);
+    }
+    let mut score = eval.of(&chosen);
+
+    if let Strategy::LocalSearch { max_swaps } = strategy {
+        for _ in 0..max_swaps {
+            let mut best = None;
+            for i in 0..k {
+                for s in 0..n {
+                    if chosen.contains(&s) {
+                        continue;
+                    }
+                    let old = chosen[i];
+                    chosen[i] = s;
+                    let swapped = eval.of(&chosen);
+                    chosen[i] = old;
+                    if swapped < best.map_or(score, |(_, _, b)| b) {
+                        best = Some((i, s, swapped));
+                    }
+                }
+            }
+            match best {
+                Some((i, s, swapped)) => {
+                    chosen[i] = s;
+                    score = swapped;
+                }
+                None => break,
+            }
+        }
+    }
+
+    let assignments = (0..clients.len())
+        .map(|c| eval.nearest(c, &chosen).0)
+        .collect();
+
+    Ok(Placement {
+        sites: chosen,
+        assignments,
+        objective: score,
+    })
+}
+
+/// Evaluates the objective over a client to site distance matrix
+struct Eval<'a> {
+    dist: &'a [f64],
+    n: usize,
+    weights: Option<&'a [f64]>,
+    total: f64,
+    objective: Objective,
+}
+
+impl Eval<'_> {
+    /// Returns the nearest chosen site to client `c` along with its distance
+    fn nearest(&self, c: usize, chosen: &[usize]) -> (usize, f64) {
+        let row = &self.dist[c * self.n..(c + 1) * self.n];
+        let mut best = (chosen[0], row[chosen[0]]);
+        for &s in &chosen[1..] {
+            if row[s] < best.1 {
+                best = (s, row[s]);
+            }
+        }
+        best
+    }
+
+    /// Returns the objective value of the sites in `chosen`
+    fn of(&self, chosen: &[usize]) -> f64 {
+        self.score((0..self.dist.len() / self.n).map(|c| self.nearest(c, chosen).1))
+    }
+
+    /// Returns the objective value given each client's distance to its nearest
+    /// replica
+    fn score(&self, nearest: impl Iterator<Item = f64>) -> f64 {
+        match self.objective {
+            Objective::Mean => {
+                nearest
+                    .enumerate()
+                    .map(|(c, d)| weight(self.weights, c) * d)
+                    .sum::<f64>()
+                    / self.total
+            }
+            Objective::Max => nearest
+                .enumerate()
+                .filter(|(c, _)| weight(self.weights, *c) > 0.0)
+                .map(|(_, d)| d)
+                .fold(0.0, f64::max),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heapless::VecD;
+
+    fn c(x: f64, y: f64) -> Coord<VecD<2>> { Coord::from(VecD::from([x, y])) }
+
+    /// Two dense groups of clients and one distant straggler
+    fn clients() -> Vec<Coord<VecD<2>>> {
+        let mut clients = Vec::new();
+        for i in 0..5 {
+            clients.push(c(0.0, i as f64 * 0.1));
+            clients.push(c(10.0, i as f64 * 0.1));
+        }
+        clients.push(c(5.0, 30.0));
+        clients
+    }
+
+    fn sites() -> Vec<Coord<VecD<2>>> {
+        vec![
+            c(0.0, 0.2),
+            c(10.0, 0.2),
+            c(5.0, 30.0),
+            c(5.0, -1.0),
+            c(5.0, 15.0),
+        ]
+    }
+
+    fn sorted(mut v: Vec<usize>) -> Vec<usize> {
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn mean_ignores_straggler() {
+        let p = place(
+            &sites(),
+            &clients(),
+            None,
+            2,
+            Objective::Mean,
+            Strategy::Greedy,
+        )
+        .unwrap();
+        assert_eq!(sorted(p.sites), vec![0, 1]);
+        assert_eq!(p.assignments[0], 0);
+        assert_eq!(p.assignments[1], 1);
+    }
+
+    #[test]
+    fn max_covers_straggler() {
+        let p = place(
+            &sites(),
+            &clients(),
+            None,
+            2,
+            Objective::Max,
+            Strategy::LocalSearch { max_swaps: 10 },
+        )
+        .unwrap();
+        assert!(p.sites.contains(&2));
+        assert_eq!(p.assignments[10], 2);
+        assert!(p.objective < 6.0);
+    }
+
+    #[test]
+    fn local_search_improves_greedy() {
+        // Greedy picks the site in the middle first and gets stuck with it
+        let sites = vec![c(5.0, 0.0), c(0.0, 0.0), c(10.0, 0.0)];
+        let clients = vec![c(0.0, 0.0), c(10.0, 0.0)];
+        let greedy = place(&sites, &clients, None, 2, Objective::Mean, Strategy::Greedy).unwrap();
+        assert!(greedy.sites.contains(&0));
+        assert_eq!(greedy.objective, 2.5);
+
+        let local = place(
+            &sites,
+            &clients,
+            None,
+            2,
+            Objective::Mean,
+            Strategy::LocalSearch { max_swaps: 10 },
+        )
+        .unwrap();
+        assert_eq!(sorted(local.sites), vec![1, 2]);
+        assert_eq!(local.objective, 0.0);
+    }
+
+    #[test]
+    fn weights() {
+        let sites = vec![c(0.0, 0.0), c(10.0, 0.0)];
+        let clients = vec![c(0.0, 0.0), c(10.0, 0.0)];
+        let p = place(
+            &sites,
+            &clients,
+            Some(&[1.0, 3.0]),
+            1,
+            Objective::Mean,
+            Strategy::Greedy,
+        )
+        .unwrap();
+        assert_eq!(p.sites, vec![1]);
+        assert_eq!(p.objective, 2.5);
+
+        // Zero weight clients don't count toward the worst case
+        let p = place(
+            &sites,
+            &clients,
+            Some(&[0.0, 1.0]),
+            1,
+            Objective::Max,
+            Strategy::Greedy,
+        )
+        .unwrap();
+        assert_eq!(p.sites, vec![1]);
+        assert_eq!(p.objective, 0.0);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let s = sites();
+        let cl = clients();
+        let g = Strategy::Greedy;
+        assert!(place(&s, &cl, None, 0, Objective::Mean, g)
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(place(&s, &cl, None, 6, Objective::Mean, g)
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(place(&s, &[], None, 1, Objective::Mean, g)
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(
+            place(&s, &cl[..2], Some(&[-1.0, 1.0]), 1, Objective::Mean, g)
+                .unwrap_err()
+                .is_invalid_weight()
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! Projecting coordinates to 2-D for visualization
+//!
+//! [`project`] flattens a set of [`Coord`]s onto a plane using either
+//! principal component analysis of the raw vectors ([`Method::Pca`]) or
+//! classical multidimensional scaling of the estimated RTTs
+//! ([`Method::Mds`]). The resulting [`Projection`] can be written out as a
+//! standalone SVG scatter plot, where each point's radius shows its error
+//! estimate and its color shows its height, or as CSV for further analysis.
+//!
+//! Output is written to any [`fmt::Write`](core::fmt::Write), such as a
+//! `String`, so this works without `std`.
+//!
+//! ```rust
+//! use violin::{
+//!     heapless::VecD,
+//!     project::{project, Method, SvgConfig},
+//!     Coord,
+//! };
+//!
+//! let coords = [
+//!     Coord::from(VecD::from([0.0, 0.0, 0.0])),
+//!     Coord::from(VecD::from([0.1, 0.0, 0.0])),
+//!     Coord::from(VecD::from([0.0, 0.1, 0.0])),
+//!     Coord::from(VecD::from([0.0, 0.0, 0.1])),
+//! ];
+//!
+//! let p = project(&coords, Method::Pca).unwrap();
+//! assert_eq!(p.points.len(), 4);
+//!
+//! let mut svg = String::new();
+//! p.write_svg(&mut svg, &SvgConfig::default()).unwrap();
+//! assert!(svg.starts_with("<svg"));
+//!
+//! let mut csv = String::new();
+//! p.write_csv(&mut csv).unwrap();
+//! assert_eq!(csv.lines().count(), 5);
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    std::fmt,
+    Coord, Vector,
+};
+
+/// The maximum number of power iterations used to find each axis
+const MAX_ITERATIONS: usize = 1000;
+
+/// How [`project`] maps coordinates onto the plane
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Method {
+    /// Principal component analysis of the raw vectors. Heights and offsets
+    /// are ignored, so the axes are the two directions the vectors vary the
+    /// most along.
+    Pca,
+    /// Classical (Torgerson) multidimensional scaling of the pairwise
+    /// [`Coord::distance_to`] estimates, so heights and offsets are taken into
+    /// account. This builds an `N×N` matrix so requires `O(N²)` memory.
+    Mds,
+}
+
+/// A single projected coordinate
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Point {
+    /// The position along the first axis
+    pub x: f64,
+    /// The position along the second axis
+    pub y: f64,
+    /// The coordinate's error estimate
+    pub error_estimate: f64,
+    /// The coordinate's height
+    pub height: f64,
+}
+
+/// The result of [`project`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+    /// The projected points, in the same order as the input coordinates
+    pub points: Vec<Point>,
+    /// The fraction of the total variance captured by each axis. A low sum
+    /// means the plot hides a lot of structure.
+    pub explained: [f64; 2],
+}
+
+/// Options for [`Projection::write_svg`]
+#[derive(Debug, Copy, Clone)]
+pub struct SvgConfig {
+    /// The width and height of the image in pixels
+    pub size: f64,
+    /// The blank space around the plot in pixels
+    pub margin: f64,
+    /// The radius of a point with an error estimate of `0.0`
+    pub min_radius: f64,
+    /// The radius of the point with the largest error estimate
+    pub max_radius: f64,
+}
+
+impl Default for SvgConfig {
+    fn default() -> Self {
+        Self {
+            size: 600.0,
+            margin: 20.0,
+            min_radius: 2.0,
+            max_radius: 12.0,
+        }
+    }
+}
+
+/// Projects `coords` to 2-D
+///
+/// The first axis is always the one capturing the most variance. Projections
+/// are only unique up to rotation and reflection, so compare distances between
+/// points rather than positions across projections.
+///
+/// # Errors
+///
+/// Returns an error if `coords` is empty
+pub fn project<T: Vector>(coords: &[Coord<T>], method: Method) -> Result<Projection> {
+    if coords.is_empty() {
+        return Err(Error {
+            kind: ErrorKind::InvalidLength,
+        });
+    }
+
+    let (xy, explained) = match method {
+        Method::Pca => pca(coords),
+        Method::Mds => mds(coords),
+    };
+
+    let points = coords
+        .iter()
+        .zip(xy)
+        .map(|(c, (x, y))| Point {
+            x,
+            y,
+            error_estimate: c.error_estimate,
+            height: c.height,
+        })
+        .collect();
+
+    Ok(Projection { points, explained })
+}
+
+impl Projection {
+    /// Writes the points as CSV with an `index,x,y,error_estimate,height`
+    /// header
+    pub fn write_csv<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "index,x,y,error_estimate,height")?;
+        for (i, p) in self.points.iter().enumerate() {
+            writeln!(w, "{},{},{},{},{}", i, p.x, p.y, p.error_estimate, p.height)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a standalone SVG scatter plot of the points
+    ///
+    /// Both axes share the same scale so distances are not distorted. Radius
+    /// grows with error estimate, and color goes from blue for the lowest
+    /// height to red for the highest. Hovering a point shows its index, height
+    /// and error estimate.
+    pub fn write_svg<W: fmt::Write>(&self, w: &mut W, cfg: &SvgConfig) -> fmt::Result {
+        let bounds = |f: fn(&Point) -> f64| {
+            self.points
+                .iter()
+                .map(f)
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+                    (lo.min(v), hi.max(v))
+                })
+        };
+        let (x_min, x_max) = bounds(|p| p.x);
+        let (y_min, y_max) = bounds(|p| p.y);
+        let (h_min, h_max) = bounds(|p| p.height);
+        let (_, e_max) = bounds(|p| p.error_estimate);
+
+        let span = f64::max(x_max - x_min, y_max - y_min);
+        let inner = cfg.size - 2.0 * cfg.margin;
+        let scale = if span > 0.0 { inner / span } else { 0.0 };
+        // Center the points along whichever axis has room to spare
+        let x0 = cfg.margin + (inner - (x_max - x_min) * scale) / 2.0;
+        let y0 = cfg.margin + (inner - (y_max - y_min) * scale) / 2.0;
+
+        writeln!(
+            w,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{s}" height="{s}" viewBox="0 0 {s} {s}">"#,
+            s = cfg.size
+        )?;
+        writeln!(w, r#"<rect width="100%" height="100%" fill="white"/>"#)?;
+        for (i, p) in self.points.iter().enumerate() {
+            let cx = x0 + (p.x - x_min) * scale;
+            // SVG's y axis points down
+            let cy = cfg.size - (y0 + (p.y - y_min) * scale);
+            let r = cfg.min_radius
+                + (cfg.max_radius - cfg.min_radius) * unit(p.error_estimate, 0.0, e_max);
+            let t = unit(p.height, h_min, h_max);
+            let red = (255.0 * t) as u8;
+            let blue = (255.0 * (1.0 - t)) as u8;
+            writeln!(
+                w,
+                r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="rgb({},0,{})" fill-opacity="0.6"><title>{}: height {} error {}</title></circle>"#,
+                cx, cy, r, red, blue, i, p.height, p.error_estimate
+            )?;
+        }
+        writeln!(w, "</svg>")
+    }
+}
+
+/// Maps `v` from `[lo, hi]` to `[0.0, 1.0]`, or `0.0` if the range is empty
+fn unit(v: f64, lo: f64, hi: f64) -> f64 {
+    if hi > lo {
+        (v - lo) / (hi - lo)
+    } else {
+        0.0
+    }
+}
+
+type Axes = (Vec<(f64, f64)>, [f64; 2]);
+
+fn pca<T: Vector>(coords: &[Coord<T>]) -> Axes {
+    let n = coords.len();
+    let d = coords[0].vec.as_ref().len();
+
+    let mut mean = vec![0.0; d];
+    for c in coords {
+        for (m, x) in mean.iter_mut().zip(c.vec.as_ref()) {
+            *m += x / n as f64;
+        }
+    }
+    let centered: Vec<Vec<f64>> = coords
+        .iter()
+        .map(|c| {
+            c.vec
+                .as_ref()
+                .iter()
+                .zip(&mean)
+                .map(|(x, m)| x - m)
+                .collect()
+        })
+        .collect();
+
+    let mut cov = vec![0.0; d * d];
+    for x in &centered {
+        for i in 0..d {
+            for j in 0..d {
+                cov[i * d + j] += x[i] * x[j] / n as f64;
+            }
+        }
+    }
+    let trace = (0..d).map(|i| cov[i * d + i]).sum::<f64>();
+
+    let (l1, v1) = top_eigen(&cov, d, 0.0);
+    deflate(&mut cov, d, l1, &v1);
+    // Anything much smaller than the first axis is rounding error left over
+    // from deflation
+    let (l2, v2) = top_eigen(&cov, d, l1 * 1.0e-9);
+
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+    let xy = centered
+        .iter()
+        .map(|x| (dot(x, &v1), dot(x, &v2)))
+        .collect();
+    (xy, [unit(l1, 0.0, trace), unit(l2, 0.0, trace)])
+}
+
+fn mds<T: Vector>(coords: &[Coord<T>]) -> Axes {
+    let n = coords.len();
+
+    // Double center the squared distances: B = -1/2 J D² J
+    let mut b = vec![0.0; n * n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let d = coords[i].distance_to(&coords[j]);
+            b[i * n + j] = d * d;
+            b[j * n + i] = d * d;
+        }
+    }
+    let row_means: Vec<f64> = (0..n)
+        .map(|i| b[i * n..(i + 1) * n].iter().sum::<f64>() / n as f64)
+        .collect();
+    let grand_mean = row_means.iter().sum::<f64>() / n as f64;
+    for i in 0..n {
+        for j in 0..n {
+            b[i * n + j] = -0.5 * (b[i * n + j] - row_means[i] - row_means[j] + grand_mean);
+        }
+    }
+    let trace = (0..n).map(|i| b[i * n + i]).sum::<f64>();
+
+    let (l1, v1) = top_eigen(&b, n, 0.0);
+    deflate(&mut b, n, l1, &v1);
+    // Anything much smaller than the first axis is rounding error left over
+    // from deflation
+    let (l2, v2) = top_eigen(&b, n, l1 * 1.0e-9);
+
+    let (s1, s2) = (crate::sqrt(l1), crate::sqrt(l2));
+    let xy = v1.iter().zip(&v2).map(|(a, b)| (a * s1, b * s2)).collect();
+    (xy, [unit(l1, 0.0, trace), unit(l2, 0.0, trace)])
+}
+
+/// Returns the largest (non-negative) eigenvalue of the symmetric `n×n`
+/// matrix `m` and its unit eigenvector, using power iteration
+///
+/// The matrix is shifted by a bound on its spectral radius so the iteration
+/// converges to the largest eigenvalue rather than the one largest in
+/// magnitude. If no eigenvalue is above `min` this returns `0.0` and a zero
+/// vector.
+fn top_eigen(m: &[f64], n: usize, min: f64) -> (f64, Vec<f64>) {
+    let shift = (0..n)
+        .map(|i| {
+            m[i * n..(i + 1) * n]
+                .iter()
+                .map(|x| crate::abs(*x))
+                .sum::<f64>()
+        })
+        .fold(0.0, f64::max);
+    if shift == 0.0 {
+        return (0.0, vec![0.0; n]);
+    }
+
+    // An uneven start avoids being orthogonal to the result, e.g. the all ones
+    // vector is always in the null space of a double centered matrix
+    let mut v: Vec<f64> = (0..n).map(|i| 1.0 + (i % 7) as f64 / 7.0).collect();
+    let mut lambda = 0.0;
+    for _ in 0..MAX_ITERATIONS {
+        let mut next: Vec<f64> = (0..n)
+            .map(|i| {
+                let row = &m[i * n..(i + 1) * n];
+                row.iter().zip(&v).map(|(a, b)| a * b).sum::<f64>() + shift * v[i]
+            })
+            .collect();
+        let norm = crate::sqrt(next.iter().map(|x| x * x).sum::<f64>());
+        if norm == 0.0 {
+            break;
+        }
+        next.iter_mut().for_each(|x| *x /= norm);
+        let moved = next
+            .iter()
+            .zip(&v)
+            .map(|(a, b)| crate::abs(a - b))
+            .fold(0.0, f64::max);
+        v = next;
+        lambda = norm - shift;
+        if moved < 1.0e-12 {
+            break;
+        }
+    }
+
+    if lambda <= min {
+        return (0.0, vec![0.0; n]);
+    }
+    (lambda, v)
+}
+
+/// Removes the eigenpair `(lambda, v)` from `m`
+fn deflate(m: &mut [f64], n: usize, lambda: f64, v: &[f64]) {
+    for i in 0..n {
+        for j in 0..n {
+            m[i * n + j] -= lambda * v[i] * v[j];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::{close, heapless::VecD};
+
+    fn planar() -> Vec<Coord<VecD<3>>> {
+        // Points on a tilted plane through 3-D space
+        [(0.0, 0.0), (3.0, 0.0), (0.0, 1.0), (3.0, 1.0), (1.0, 0.5)]
+            .iter()
+            .map(|(a, b)| Coord::from(VecD::from([*a, *b, *a + *b])))
+            .collect()
+    }
+
+    fn dist(a: &Point, b: &Point) -> f64 {
+        let (dx, dy) = (a.x - b.x, a.y - b.y);
+        crate::sqrt(dx * dx + dy * dy)
+    }
+
+    #[test]
+    fn pca_preserves_planar_distances() {
+        let coords = planar();
+        let p = project(&coords, Method::Pca).unwrap();
+        for i in 0..coords.len() {
+            for j in 0..coords.len() {
+                assert!(close(
+                    dist(&p.points[i], &p.points[j]),
+                    coords[i].distance_to(&coords[j])
+                ));
+            }
+        }
+        assert!(close(p.explained[0] + p.explained[1], 1.0));
+        assert!(p.explained[0] >= p.explained[1]);
+    }
+
+    #[test]
+    fn mds_preserves_planar_distances() {
+        // Heights separate otherwise overlapping coordinates, but only for MDS
+        let mut coords = vec![Coord::from(VecD::from([1.0, 1.0])); 2];
+        coords[1].set_height(0.5);
+        let p = project(&coords, Method::Mds).unwrap();
+        assert!(close(dist(&p.points[0], &p.points[1]), 0.5));
+        assert_eq!(p.points[1].height, 0.5);
+        let p = project(&coords, Method::Pca).unwrap();
+        assert_eq!(dist(&p.points[0], &p.points[1]), 0.0);
+
+        let p = project(&planar(), Method::Mds).unwrap();
+        let coords = planar();
+        for i in 0..coords.len() {
+            for j in 0..coords.len() {
+                assert!(close(
+                    dist(&p.points[i], &p.points[j]),
+                    coords[i].distance_to(&coords[j])
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn degenerate() {
+        // A single point, and one-dimensional input with no second axis
+        let p = project(&[Coord::from(VecD::from([1.0, 2.0]))], Method::Pca).unwrap();
+        assert_eq!((p.points[0].x, p.points[0].y), (0.0, 0.0));
+
+        let line = [
+            Coord::from(VecD::from([0.0])),
+            Coord::from(VecD::from([2.0])),
+        ];
+        for m in [Method::Pca, Method::Mds] {
+            let p = project(&line, m).unwrap();
+            assert!(close(dist(&p.points[0], &p.points[1]), 2.0));
+            assert_eq!(p.explained[1], 0.0);
+        }
+
+        let empty: [Coord<VecD<2>>; 0] = [];
+        assert!(project(&empty, Method::Pca)
+            .unwrap_err()
+            .is_invalid_length());
+    }
+
+    #[test]
+    fn export() {
+        let mut coords = planar();
+        coords[0].set_error_estimate(1.5);
+        coords[2].set_height(1.0);
+        let p = project(&coords, Method::Pca).unwrap();
+
+        let mut csv = String::new();
+        p.write_csv(&mut csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], "index,x,y,error_estimate,height");
+        assert_eq!(lines.len(), 6);
+        assert!(lines[1].starts_with("0,") && lines[1].ends_with(",1.5,0"));
+
+        let mut svg = String::new();
+        p.write_svg(&mut svg, &SvgConfig::default()).unwrap();
+        assert!(svg.starts_with("<svg") && svg.trim_end().ends_with("</svg>"));
+        assert_eq!(svg.matches("<circle").count(), 5);
+        // Largest error estimate gets the largest radius, highest is red
+        assert!(svg.contains(r#"r="12.00" fill="rgb(0,0,255)""#));
+        assert!(svg.contains(r#"fill="rgb(255,0,0)""#));
+    }
+}
@@ -0,0 +1,199 @@
+//! Ranking candidates by estimated latency
+//!
+//! Used through [`Coord::rank`] and [`Node::rank`](crate::Node::rank), which
+//! return the `k` candidates with the lowest estimated RTT. Candidates whose
+//! scores are within [`RankConfig::tolerance`] of one another are considered
+//! tied and ordered by lower error estimate, so a confident estimate is
+//! preferred over an equally close but uncertain one.
+//!
+//! ```rust
+//! use violin::{heapless::VecD, rank::RankConfig, Coord};
+//!
+//! let me = Coord::from(VecD::from([0.0, 0.0]));
+//! let mut a = Coord::from(VecD::from([0.010, 0.0]));
+//! let mut b = Coord::from(VecD::from([0.011, 0.0]));
+//! let c = Coord::from(VecD::from([0.050, 0.0]));
+//! a.set_error_estimate(0.9);
+//! b.set_error_estimate(0.1);
+//!
+//! let cfg = RankConfig {
+//!     tolerance: 0.002,
+//!     ..Default::default()
+//! };
+//! let top = me.rank([("a", &a), ("b", &b), ("c", &c)], 2, &cfg);
+//! // `a` and `b` are tied within 2ms, so the more confident `b` wins
+//! assert_eq!(top[0].id, "b");
+//! assert_eq!(top[1].id, "a");
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{
+    std::{borrow::Borrow, cmp::Ordering},
+    Coord, Vector,
+};
+
+/// Tunables for ranking candidates
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RankConfig {
+    /// Candidates whose scores differ by no more than this (in the same units
+    /// as the estimates, i.e. seconds for a [`Node`](crate::Node)) are tied and
+    /// ordered by lower error estimate instead
+    pub tolerance: f64,
+
+    /// Penalizes uncertain candidates by scoring them as
+    /// `rtt * (1 + uncertainty_penalty * error_estimate)`, using the
+    /// candidate's error estimate. `0.0` ranks purely by estimated RTT.
+    pub uncertainty_penalty: f64,
+}
+
+/// A ranked candidate
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ranked<I, T = f64> {
+    /// The candidate's identifier as given
+    pub id: I,
+    /// The estimated RTT to the candidate
+    pub rtt: T,
+    /// The candidate's error estimate
+    pub error_estimate: f64,
+    /// The score candidates were ranked by, which is the estimated RTT in
+    /// seconds adjusted by [`RankConfig::uncertainty_penalty`]
+    pub score: f64,
+}
+
+pub(crate) fn rank<T, I, C>(
+    origin: &Coord<T>,
+    candidates: impl IntoIterator<Item = (I, C)>,
+    k: usize,
+    cfg: &RankConfig,
+) -> Vec<Ranked<I>>
+where
+    T: Vector,
+    C: Borrow<Coord<T>>,
+{
+    let mut ranked: Vec<_> = candidates
+        .into_iter()
+        .map(|(id, c)| {
+            let c = c.borrow();
+            let rtt = origin.distance_to(c);
+            Ranked {
+                id,
+                rtt,
+                error_estimate: c.error_estimate,
+                score: rtt * (1.0 + cfg.uncertainty_penalty * c.error_estimate),
+            }
+        })
+        .collect();
+    ranked.sort_by(|a, b| cmp(a.score, b.score));
+
+    // Reorder each run of tied candidates by error estimate. A run extends
+    // while scores are within tolerance of the run's lowest score.
+    let mut start = 0;
+    while start < k.min(ranked.len()) {
+        let base = ranked[start].score;
+        let len = ranked[start..]
+            .iter()
+            .take_while(|r| r.score - base <= cfg.tolerance)
+            .count()
+            .max(1);
+        ranked[start..start + len]
+            .sort_by(|a, b| cmp(a.error_estimate, b.error_estimate).then(cmp(a.score, b.score)));
+        start += len;
+    }
+
+    ranked.truncate(k);
+    ranked
+}
+
+/// Orders floats ascending with NaN last
+fn cmp(a: f64, b: f64) -> Ordering {
+    a.partial_cmp(&b)
+        .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan()))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+    use crate::{close, heapless::VecD};
+
+    fn candidates() -> Vec<(usize, Coord<VecD<1>>)> {
+        [
+            (0.030, 0.2),
+            (0.010, 0.5),
+            (0.020, 0.1),
+            (0.0105, 0.05),
+            (0.040, 0.01),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, (x, e))| {
+            let mut c = Coord::from(VecD::from([*x]));
+            c.set_error_estimate(*e);
+            (i, c)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn top_k_by_rtt() {
+        let me = Coord::new();
+        let top = me.rank(candidates(), 3, &RankConfig::default());
+        let ids: Vec<_> = top.iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![1, 3, 2]);
+        assert!(close(top[0].rtt, 0.010));
+    }
+
+    #[test]
+    fn tie_break_on_error() {
+        let me = Coord::new();
+        let cfg = RankConfig {
+            tolerance: 0.001,
+            ..Default::default()
+        };
+        let ids: Vec<_> = me
+            .rank(candidates(), 5, &cfg)
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, vec![3, 1, 2, 0, 4]);
+
+        // Exact ties always prefer the lower error estimate
+        let mut a = Coord::from(VecD::from([1.0]));
+        let mut b = a.clone();
+        a.set_error_estimate(0.5);
+        b.set_error_estimate(0.2);
+        let top = me.rank([('a', &a), ('b', &b)], 1, &RankConfig::default());
+        assert_eq!(top[0].id, 'b');
+    }
+
+    #[test]
+    fn uncertainty_penalty() {
+        let me = Coord::new();
+        let cfg = RankConfig {
+            uncertainty_penalty: 2.0,
+            ..Default::default()
+        };
+        let ids: Vec<_> = me
+            .rank(candidates(), 5, &cfg)
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        // 0 (0.042) is now scored behind 4 (0.0408)
+        assert_eq!(ids, vec![3, 1, 2, 4, 0]);
+    }
+
+    #[test]
+    fn fewer_than_k() {
+        let me = Coord::new();
+        assert_eq!(me.rank(candidates(), 10, &RankConfig::default()).len(), 5);
+        assert!(me
+            .rank(
+                Vec::<(usize, Coord<VecD<1>>)>::new(),
+                3,
+                &RankConfig::default()
+            )
+            .is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+//! Greedy next-hop selection for routing toward a destination coordinate
+//!
+//! In greedy routing each node forwards a message to whichever of its
+//! neighbors is estimated to be closest to the destination. Because every hop
+//! strictly reduces the estimated distance remaining, greedy routes can't
+//! loop; however they can get stuck at a *local minimum*, a node where no
+//! neighbor is any closer to the destination than the node itself.
+//!
+//! [`next_hop`] never hides a local minimum. It reports either a greedy hop,
+//! a hop chosen by the [`Fallback`] strategy (which is always flagged as such),
+//! or [`NextHop::LocalMinimum`] when there is nowhere left to go.
+//!
+//! ```rust
+//! use violin::{
+//!     heapless::VecD,
+//!     route::{next_hop, Fallback, NextHop},
+//!     Coord,
+//! };
+//!
+//! let me = Coord::from(VecD::from([0.0, 0.0]));
+//! let dest = Coord::from(VecD::from([10.0, 0.0]));
+//! let neighbors = [
+//!     (1, Coord::from(VecD::from([-2.0, 0.0]))),
+//!     (2, Coord::from(VecD::from([3.0, 1.0]))),
+//!     (3, Coord::from(VecD::from([4.0, 0.0]))),
+//! ];
+//!
+//! let hop = next_hop(
+//!     &me,
+//!     &dest,
+//!     neighbors.iter().map(|(i, c)| (*i, c)),
+//!     &[],
+//!     Fallback::Stop,
+//! );
+//! assert!(matches!(hop, NextHop::Greedy { id: 3, .. }));
+//! ```
+
+use crate::{std::borrow::Borrow, Coord, Vector};
+
+/// What to do when no neighbor is closer to the destination than the current
+/// node
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fallback {
+    /// Report [`NextHop::LocalMinimum`]
+    Stop,
+    /// Forward to the unvisited neighbor closest to the destination even
+    /// though it makes no progress, reported as [`NextHop::Fallback`]. The
+    /// route can only continue as long as the caller passes the nodes the
+    /// message has already been through as `visited`, otherwise routes may
+    /// bounce between two nodes.
+    LeastRegress,
+}
+
+/// The result of [`next_hop`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NextHop<I> {
+    /// Forward to neighbor `id`, which is estimated to be `progress` closer to
+    /// the destination than the current node
+    Greedy {
+        /// The chosen neighbor's identifier
+        id: I,
+        /// How much closer (always positive) the neighbor is to the
+        /// destination than the current node
+        progress: f64,
+    },
+    /// The current node is a local minimum and the [`Fallback`] strategy chose
+    /// neighbor `id`
+    Fallback {
+        /// The chosen neighbor's identifier
+        id: I,
+        /// How much closer the neighbor is to the destination than the
+        /// current node, which is zero or negative
+        progress: f64,
+    },
+    /// The current node is a local minimum and there is no neighbor to fall
+    /// back to
+    LocalMinimum,
+}
+
+impl<I> NextHop<I> {
+    /// Returns the chosen neighbor, if any
+    pub fn id(&self) -> Option<&I> {
+        match self {
+            NextHop::Greedy { id, .. } | NextHop::Fallback { id, .. } => Some(id),
+            NextHop::LocalMinimum => None,
+        }
+    }
+
+    /// Returns true if the current node is a local minimum, regardless of
+    /// whether a fallback hop was chosen
+    pub fn is_local_minimum(&self) -> bool { !matches!(self, NextHop::Greedy { .. }) }
+}
+
+/// Picks the neighbor making the most progress toward `dest`
+///
+/// Progress is the estimated distance from `current` to `dest` minus the
+/// estimated distance from the neighbor to `dest`, using
+/// [`Coord::distance_to`]. Neighbors whose identifier is in `visited` are never
+/// chosen.
+pub fn next_hop<T, I, C>(
+    current: &Coord<T>,
+    dest: &Coord<T>,
+    neighbors: impl IntoIterator<Item = (I, C)>,
+    visited: &[I],
+    fallback: Fallback,
+) -> NextHop<I>
+where
+    T: Vector,
+    I: PartialEq,
+    C: Borrow<Coord<T>>,
+{
+    let remaining = current.distance_to(dest);
+
+    let mut best: Option<(I, f64)> = None;
+    for (id, c) in neighbors {
+        if visited.contains(&id) {
+            continue;
+        }
+        let progress = remaining - c.borrow().distance_to(dest);
+        if best.as_ref().map_or(true, |(_, p)| progress > *p) {
+            best = Some((id, progress));
+        }
+    }
+
+    match best {
+        Some((id, progress)) if progress > 0.0 => NextHop::Greedy { id, progress },
+        Some((id, progress)) if fallback == Fallback::LeastRegress => {
+            NextHop::Fallback { id, progress }
+        }
+        _ => NextHop::LocalMinimum,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heapless::VecD;
+
+    fn c(x: f64, y: f64) -> Coord<VecD<2>> { Coord::from(VecD::from([x, y])) }
+
+    #[test]
+    fn greedy() {
+        let neighbors = [(1, c(1.0, 0.0)), (2, c(2.0, 0.0)), (3, c(0.0, 5.0))];
+        let hop = next_hop(
+            &c(0.0, 0.0),
+            &c(5.0, 0.0),
+            neighbors.iter().map(|(i, c)| (*i, c)),
+            &[],
+            Fallback::Stop,
+        );
+        assert_eq!(
+            hop,
+            NextHop::Greedy {
+                id: 2,
+                progress: 2.0
+            }
+        );
+        assert!(!hop.is_local_minimum());
+    }
+
+    #[test]
+    fn local_minimum() {
+        // Every neighbor is behind us, the destination is across a void
+        let neighbors = [(1, c(-1.0, 0.0)), (2, c(-1.0, -3.0))];
+        let me = c(0.0, 0.0);
+        let dest = c(5.0, 0.0);
+        let iter = || neighbors.iter().map(|(i, c)| (*i, c));
+
+        let hop = next_hop(&me, &dest, iter(), &[], Fallback::Stop);
+        assert_eq!(hop, NextHop::LocalMinimum);
+        assert_eq!(hop.id(), None);
+
+        let hop = next_hop(&me, &dest, iter(), &[], Fallback::LeastRegress);
+        assert_eq!(
+            hop,
+            NextHop::Fallback {
+                id: 1,
+                progress: -1.0
+            }
+        );
+        assert!(hop.is_local_minimum());
+
+        // The fallback never goes back the way we came
+        let hop = next_hop(&me, &dest, iter(), &[1], Fallback::LeastRegress);
+        assert_eq!(hop.id(), Some(&2));
+        let hop = next_hop(&me, &dest, iter(), &[1, 2], Fallback::LeastRegress);
+        assert_eq!(hop, NextHop::LocalMinimum);
+    }
+
+    #[test]
+    fn visited_skipped_when_greedy() {
+        let neighbors = [(1, c(4.0, 0.0)), (2, c(2.0, 0.0))];
+        let hop = next_hop(
+            &c(0.0, 0.0),
+            &c(5.0, 0.0),
+            neighbors.iter().map(|(i, c)| (*i, c)),
+            &[1],
+            Fallback::Stop,
+        );
+        assert_eq!(hop.id(), Some(&2));
+    }
+
+    #[test]
+    fn no_neighbors() {
+        let hop = next_hop::<VecD<2>, usize, Coord<VecD<2>>>(
+            &c(0.0, 0.0),
+            &c(5.0, 0.0),
+            [],
+            &[],
+            Fallback::LeastRegress,
+        );
+        assert_eq!(hop, NextHop::LocalMinimum);
+    }
+}
@@ -0,0 +1,519 @@
+//! A discrete-event simulator for evaluating [`Config`]s
+//!
+//! A [`Simulation`] runs a set of [`Node`]s against a ground truth
+//! [`RttMatrix`] on a virtual clock. Every node probes a random peer once per
+//! [`SimConfig::probe_interval`] and feeds the (noisy) measured RTT to
+//! [`Node::update`], exactly as a real deployment would. Probes may be lost,
+//! and nodes may leave and later rejoin with a fresh coordinate.
+//!
+//! All randomness comes from the caller supplied RNG, so a run is reproducible
+//! given a seeded RNG.
+//!
+//! Every [`SimConfig::sample_interval`] the simulation records a [`Sample`]
+//! holding each node's median relative error against the ground truth.
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use rand::{rngs::StdRng, SeedableRng};
+//! use violin::{
+//!     heapless::VecD,
+//!     matrix::RttMatrix,
+//!     sim::{SimConfig, Simulation},
+//!     Coord,
+//! };
+//!
+//! // The ground truth is a 2-D plane, up to 100ms across
+//! let truth = RttMatrix::from_coords(
+//!     &(0..20)
+//!         .map(|i| Coord::from(VecD::from([(i % 5) as f64 * 0.025, (i / 5) as f64 * 0.025])))
+//!         .collect::<Vec<_>>(),
+//! );
+//!
+//! let cfg = SimConfig {
+//!     duration: Duration::from_secs(120),
+//!     noise: 0.1,
+//!     ..Default::default()
+//! };
+//! let mut sim = Simulation::<_, VecD<3>>::new(&truth, cfg, StdRng::seed_from_u64(7)).unwrap();
+//! sim.run();
+//!
+//! let first = sim.samples().first().unwrap().median_error().unwrap();
+//! let last = sim.samples().last().unwrap().median_error().unwrap();
+//! assert!(last < first);
+//! ```
+
+use alloc::{collections::BinaryHeap, vec, vec::Vec};
+
+use rand::Rng;
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    matrix::RttMatrix,
+    metrics::Distribution,
+    std::{cmp::Reverse, time::Duration},
+    window::Window,
+    Config, Coord, Node, Vector,
+};
+
+/// Tunables for a [`Simulation`]
+#[derive(Debug, Copy, Clone)]
+pub struct SimConfig {
+    /// The configuration given to every node
+    pub node: Config,
+
+    /// How much virtual time [`Simulation::run`] simulates
+    pub duration: Duration,
+
+    /// How often each node probes a random peer. Each node's first probe
+    /// happens at a random time within the first interval.
+    pub probe_interval: Duration,
+
+    /// Measured RTTs are the true RTT multiplied by a random factor between
+    /// `1.0` and `1.0 + noise`, modelling queueing delay
+    pub noise: f64,
+
+    /// The probability a probe is lost and no update is made
+    pub loss: f64,
+
+    /// The probability a node leaves the network instead of making a probe.
+    /// Nodes that left don't answer probes, and rejoin with a fresh coordinate
+    /// after [`SimConfig::downtime`].
+    pub churn: f64,
+
+    /// How long nodes that left stay offline
+    pub downtime: Duration,
+
+    /// How often a [`Sample`] is recorded, starting at time zero
+    pub sample_interval: Duration,
+
+    /// Whether each [`Sample`] also keeps a copy of every node's coordinate
+    pub snapshots: bool,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            node: Config::default(),
+            duration: Duration::from_secs(300),
+            probe_interval: Duration::from_secs(1),
+            noise: 0.0,
+            loss: 0.0,
+            churn: 0.0,
+            downtime: Duration::from_secs(30),
+            sample_interval: Duration::from_secs(10),
+            snapshots: false,
+        }
+    }
+}
+
+/// The state of the simulation at a point in virtual time
+#[derive(Debug, Clone)]
+pub struct Sample<V> {
+    /// When the sample was taken
+    pub time: Duration,
+
+    /// Each node's median relative error, `|estimate - rtt| / rtt`, over its
+    /// online peers with a known, non-zero RTT. `None` for nodes that are
+    /// offline or have no such peers.
+    pub errors: Vec<Option<f64>>,
+
+    /// Every node's coordinate, if [`SimConfig::snapshots`] is set. Offline
+    /// nodes keep their last coordinate.
+    pub coords: Vec<Coord<V>>,
+}
+
+impl<V> Sample<V> {
+    /// Returns the median of the nodes' errors, ignoring nodes without one
+    pub fn median_error(&self) -> Option<f64> {
+        Distribution::new(self.errors.iter().flatten().copied()).map(|d| d.median)
+    }
+}
+
+/// Counters of what happened during a [`Simulation`]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SimStats {
+    /// Probes sent
+    pub probes: u64,
+    /// Probes that were lost, went to an offline node or to a peer with no
+    /// known RTT
+    pub lost: u64,
+    /// Successful calls to [`Node::update`]
+    pub updates: u64,
+    /// Calls to [`Node::update`] that reset the coordinate or were rejected
+    /// by the node's [`Policy`](crate::Policy)
+    pub resets: u64,
+    /// Times a node left the network
+    pub departures: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    Sample,
+    Probe(usize),
+    Rejoin(usize),
+}
+
+/// A discrete-event simulation of [`Node`]s on a virtual clock
+///
+/// `R` is the source of randomness, `V` and `A` are the [`Node`]'s coordinate
+/// vector and adjustment window.
+#[derive(Debug)]
+pub struct Simulation<'a, R, V, A = crate::heapless::Window<0>> {
+    truth: &'a RttMatrix,
+    cfg: SimConfig,
+    rng: R,
+    nodes: Vec<Node<V, A>>,
+    online: Vec<bool>,
+    now: Duration,
+    seq: u64,
+    queue: BinaryHeap<Reverse<(Duration, u64, Event)>>,
+    samples: Vec<Sample<V>>,
+    stats: SimStats,
+}
+
+impl<'a, R, V, A> Simulation<'a, R, V, A>
+where
+    R: Rng,
+    V: Vector + Clone,
+    A: Window,
+{
+    /// Creates a simulation with one node per row of `truth`, all starting
+    /// with a fresh coordinate at time zero
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any:
+    ///
+    /// - `truth` has fewer than two nodes
+    /// - `probe_interval` or `sample_interval` is zero
+    /// - `noise` is negative, NaN or Infinite
+    /// - `loss` or `churn` is not within `[0.0, 1.0]`
+    pub fn new(truth: &'a RttMatrix, cfg: SimConfig, mut rng: R) -> Result<Self> {
+        if truth.len() < 2 {
+            return Err(Error {
+                kind: ErrorKind::InvalidLength,
+            });
+        }
+        if cfg.probe_interval.is_zero()
+            || cfg.sample_interval.is_zero()
+            || !(cfg.noise.is_finite() && cfg.noise >= 0.0)
+            || !(0.0..=1.0).contains(&cfg.loss)
+            || !(0.0..=1.0).contains(&cfg.churn)
+        {
+            return Err(Error {
+                kind: ErrorKind::InvalidConfig,
+            });
+        }
+
+        let n = truth.len();
+        let nodes = (0..n)
+            .map(|_| Node::with_cfg_and_rng(cfg.node, &mut rng))
+            .collect();
+        let mut sim = Self {
+            truth,
+            cfg,
+            rng,
+            nodes,
+            online: vec![true; n],
+            now: Duration::ZERO,
+            seq: 0,
+            queue: BinaryHeap::new(),
+            samples: Vec::new(),
+            stats: SimStats::default(),
+        };
+
+        sim.schedule(Duration::ZERO, Event::Sample);
+        for i in 0..n {
+            let start = cfg.probe_interval.mul_f64(sim.rng.gen::<f64>());
+            sim.schedule(start, Event::Probe(i));
+        }
+
+        Ok(sim)
+    }
+
+    /// Runs the simulation until [`SimConfig::duration`]
+    pub fn run(&mut self) { self.run_until(self.cfg.duration) }
+
+    /// Processes every event up to and including virtual time `t`, which
+    /// allows inspecting or changing nodes part way through a run
+    pub fn run_until(&mut self, t: Duration) {
+        while let Some(Reverse((at, _, event))) = self.queue.peek().copied() {
+            if at > t {
+                break;
+            }
+            self.queue.pop();
+            self.now = at;
+            match event {
+                Event::Sample => self.sample(),
+                Event::Probe(i) => self.probe(i),
+                Event::Rejoin(i) => {
+                    // A node rejoins with a fresh coordinate but keeps its
+                    // policy and overlap handling
+                    let policy = self.nodes[i].policy();
+                    let overlap = self.nodes[i].overlap();
+                    self.nodes[i] = Node::with_cfg_and_rng(self.cfg.node, &mut self.rng);
+                    self.nodes[i].set_policy(policy);
+                    self.nodes[i].set_overlap(overlap);
+                    self.online[i] = true;
+                    self.schedule(self.now + self.cfg.probe_interval, Event::Probe(i));
+                }
+            }
+        }
+        self.now = self.now.max(t);
+    }
+
+    /// Returns the current virtual time
+    pub fn now(&self) -> Duration { self.now }
+
+    /// Returns the nodes, indexed the same as the ground truth
+    pub fn nodes(&self) -> &[Node<V, A>] { &self.nodes }
+
+    /// Returns the nodes, which may be changed between calls to
+    /// [`Simulation::run_until`]
+    pub fn nodes_mut(&mut self) -> &mut [Node<V, A>] { &mut self.nodes }
+
+    /// Returns true if node `i` is currently part of the network
+    pub fn is_online(&self, i: usize) -> bool { self.online[i] }
+
+    /// Returns the samples recorded so far, oldest first
+    pub fn samples(&self) -> &[Sample<V>] { &self.samples }
+
+    /// Returns counters of what has happened so far
+    pub fn stats(&self) -> SimStats { self.stats }
+
+    fn schedule(&mut self, at: Duration, event: Event) {
+        self.queue.push(Reverse((at, self.seq, event)));
+        self.seq += 1;
+    }
+
+    fn probe(&mut self, i: usize) {
+        if self.rng.gen_bool(self.cfg.churn) {
+            self.online[i] = false;
+            self.stats.departures += 1;
+            self.schedule(self.now + self.cfg.downtime, Event::Rejoin(i));
+            return;
+        }
+        self.schedule(self.now + self.cfg.probe_interval, Event::Probe(i));
+
+        // Any peer but ourselves
+        let n = self.nodes.len();
+        let j = (i + self.rng.gen_range(1..n)) % n;
+        self.stats.probes += 1;
+        let lost = self.rng.gen_bool(self.cfg.loss);
+        let rtt = match self.truth.get(i, j) {
+            Some(rtt) if self.online[j] && !lost => rtt,
+            _ => {
+                self.stats.lost += 1;
+                return;
+            }
+        };
+
+        let measured = rtt * (1.0 + self.cfg.noise * self.rng.gen::<f64>());
+        let other = self.nodes[j].coordinate().clone();
+        self.nodes[i].set_now(self.now);
+        if self.nodes[i].update(Duration::from_secs_f64(measured), &other) {
+            self.stats.updates += 1;
+        } else {
+            self.stats.resets += 1;
+        }
+    }
+
+    fn sample(&mut self) {
+        let n = self.nodes.len();
+        let errors = (0..n)
+            .map(|i| {
+                if !self.online[i] {
+                    return None;
+                }
+                let me = self.nodes[i].coordinate();
+                let rel = (0..n)
+                    .filter(|j| *j != i && self.online[*j])
+                    .filter_map(|j| {
+                        let rtt = self.truth.get(i, j).filter(|rtt| *rtt > 0.0)?;
+                        let diff = me.distance_to(self.nodes[j].coordinate()) - rtt;
+                        Some(crate::abs(diff) / rtt)
+                    });
+                Distribution::new(rel).map(|d| d.median)
+            })
+            .collect();
+
+        let coords = if self.cfg.snapshots {
+            self.nodes.iter().map(|n| n.coordinate().clone()).collect()
+        } else {
+            Vec::new()
+        };
+
+        self.samples.push(Sample {
+            time: self.now,
+            errors,
+            coords,
+        });
+        self.schedule(self.now + self.cfg.sample_interval, Event::Sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::heapless::VecD;
+
+    fn grid(n: usize) -> RttMatrix {
+        let coords: Vec<_> = (0..n * n)
+            .map(|i| Coord::from(VecD::from([(i % n) as f64 * 0.01, (i / n) as f64 * 0.01])))
+            .collect();
+        RttMatrix::from_coords(&coords)
+    }
+
+    type Sim<'a> = Simulation<'a, StdRng, VecD<3>>;
+
+    #[test]
+    fn converges() {
+        let truth = grid(5);
+        let cfg = SimConfig {
+            duration: Duration::from_secs(200),
+            ..Default::default()
+        };
+        let mut sim = Sim::new(&truth, cfg, StdRng::seed_from_u64(1)).unwrap();
+        sim.run();
+
+        let samples = sim.samples();
+        assert_eq!(samples.len(), 21);
+        assert_eq!(samples[20].time, Duration::from_secs(200));
+        // every node starts at the origin, estimating only the minimum heights
+        assert!(samples[0]
+            .errors
+            .iter()
+            .all(|e| e.map_or(false, |e| (0.99..=1.0).contains(&e))));
+        assert!(samples[20].median_error().unwrap() < 0.4);
+        assert!(samples[20].coords.is_empty());
+
+        let stats = sim.stats();
+        assert_eq!(stats.probes, 25 * 200);
+        assert_eq!(stats.updates, stats.probes);
+    }
+
+    /// Hosts behind access links as slow as the distances between them can
+    /// only be embedded by learning heights
+    #[test]
+    fn access_links() {
+        use crate::topology::{self, GeometricConfig};
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let truth = topology::geometric(
+            &GeometricConfig {
+                hosts: 30,
+                dimensions: 2,
+                diameter: 0.05,
+                height: (0.02, 0.05),
+            },
+            &mut rng,
+        )
+        .unwrap();
+        let cfg = SimConfig {
+            duration: Duration::from_secs(300),
+            ..Default::default()
+        };
+        let mut sim = Simulation::<StdRng, VecD<2>>::new(&truth, cfg, rng).unwrap();
+        sim.run();
+
+        // Updating from the vector distance alone used to blow up here
+        assert!(sim.samples().last().unwrap().median_error().unwrap() < 0.1);
+        // Most of the access link delay ends up in the heights
+        let learned = sim
+            .nodes()
+            .iter()
+            .filter(|n| (0.01..0.1).contains(&n.coordinate().height()))
+            .count();
+        assert!(learned > 25);
+    }
+
+    #[test]
+    fn reproducible() {
+        let truth = grid(4);
+        let cfg = SimConfig {
+            duration: Duration::from_secs(60),
+            noise: 0.2,
+            loss: 0.1,
+            churn: 0.01,
+            snapshots: true,
+            ..Default::default()
+        };
+        let run = |seed| {
+            let mut sim = Sim::new(&truth, cfg, StdRng::seed_from_u64(seed)).unwrap();
+            sim.run();
+            (sim.stats(), sim.samples().last().unwrap().errors.clone())
+        };
+        assert_eq!(run(3), run(3));
+        assert_ne!(run(3), run(4));
+    }
+
+    #[test]
+    fn loss_and_churn() {
+        let truth = grid(4);
+        let cfg = SimConfig {
+            duration: Duration::from_secs(100),
+            loss: 0.5,
+            churn: 0.05,
+            downtime: Duration::from_secs(1000),
+            snapshots: true,
+            ..Default::default()
+        };
+        let mut sim = Sim::new(&truth, cfg, StdRng::seed_from_u64(5)).unwrap();
+        sim.run_until(Duration::from_secs(50));
+        assert_eq!(sim.now(), Duration::from_secs(50));
+        sim.run();
+
+        let stats = sim.stats();
+        assert!(stats.departures > 0);
+        assert!(stats.lost > stats.probes / 2);
+        let last = sim.samples().last().unwrap();
+        assert_eq!(last.coords.len(), 16);
+        for (i, e) in last.errors.iter().enumerate() {
+            assert_eq!(e.is_some(), sim.is_online(i));
+        }
+    }
+
+    #[test]
+    fn missing_truth() {
+        let mut truth = grid(2);
+        truth.remove(0, 1);
+        let cfg = SimConfig {
+            duration: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let mut sim = Sim::new(&truth, cfg, StdRng::seed_from_u64(0)).unwrap();
+        sim.run();
+        assert!(sim.stats().lost > 0);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let truth = grid(2);
+        let rng = || StdRng::seed_from_u64(0);
+        let bad = [
+            SimConfig {
+                probe_interval: Duration::ZERO,
+                ..Default::default()
+            },
+            SimConfig {
+                noise: -1.0,
+                ..Default::default()
+            },
+            SimConfig {
+                loss: 1.5,
+                ..Default::default()
+            },
+        ];
+        for cfg in bad {
+            assert!(Sim::new(&truth, cfg, rng())
+                .unwrap_err()
+                .is_invalid_config());
+        }
+        assert!(Sim::new(&RttMatrix::new(1), SimConfig::default(), rng())
+            .unwrap_err()
+            .is_invalid_length());
+    }
+}
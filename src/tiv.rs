@@ -0,0 +1,341 @@
+//! Detection of triangle inequality violations (TIVs)
+//!
+//! Real networks routinely contain links where the measured RTT between two
+//! nodes is much longer than going through some third node. No metric
+//! coordinate space can represent such a link, so every update made over it
+//! drags both ends of the link away from where the rest of their measurements
+//! would place them. See [Towards Network Triangle Inequality Violation Aware
+//! Distributed Systems](https://www.cs.rice.edu/~eugeneng/papers/IMC07.pdf).
+//!
+//! [`analyze`] takes the measured RTTs between pairs of nodes along with each
+//! node's [`Coord`] and scores every measured edge by comparing its RTT to the
+//! shortest two-hop detour. Each leg of a detour uses the measured RTT when one
+//! is available, falling back to the coordinates' estimate otherwise.
+//!
+//! The resulting [`TivReport`] can then supply a weight for each edge to
+//! [`Node::update_weighted`](crate::Node::update_weighted) so updates over
+//! flagged edges are down-weighted or skipped entirely.
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use violin::{heapless::VecD, tiv, Coord, Node};
+//!
+//! let coords = vec![Coord::<VecD<2>>::new(); 3];
+//! // Node 0 to 1 is far slower than going through node 2
+//! let rtts = [(0, 1, 0.100), (0, 2, 0.010), (2, 1, 0.010)];
+//!
+//! let report = tiv::analyze(&coords, &rtts, &tiv::TivConfig::default()).unwrap();
+//! assert!(report.is_flagged(0, 1));
+//! assert!(!report.is_flagged(0, 2));
+//!
+//! // Node 0 skips the update over the flagged edge
+//! let mut node = Node::<VecD<2>>::new();
+//! node.update_weighted(Duration::from_millis(100), &coords[1], report.weight(0, 1));
+//! assert_eq!(node.coordinate().raw_coord(), coords[0].raw_coord());
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    matrix, Coord, Vector,
+};
+
+/// Tunables for [`analyze`]
+#[derive(Debug, Copy, Clone)]
+pub struct TivConfig {
+    /// An edge is flagged when its severity (measured RTT divided by the
+    /// shortest detour through another node) is at least this value
+    pub severity_threshold: f64,
+
+    /// The update weight given to flagged edges by [`TivReport::weight`].
+    /// `0.0` skips updates over flagged edges entirely.
+    pub flagged_weight: f64,
+}
+
+impl Default for TivConfig {
+    fn default() -> Self {
+        Self {
+            severity_threshold: 1.5,
+            flagged_weight: 0.0,
+        }
+    }
+}
+
+/// A single measured edge between two nodes
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TivEdge {
+    /// The lower node index of the edge
+    pub a: usize,
+    /// The higher node index of the edge
+    pub b: usize,
+    /// The measured RTT. If the edge was measured more than once, this is the
+    /// lowest RTT seen
+    pub rtt: f64,
+    /// The measured RTT divided by the shortest detour through another node.
+    /// Values above `1.0` violate the triangle inequality. This is `0.0` when
+    /// there are no other nodes to detour through
+    pub severity: f64,
+    /// The node the shortest detour goes through, if any
+    pub via: Option<usize>,
+    /// The coordinates' estimated RTT divided by the measured RTT. Edges
+    /// causing TIVs tend to be underestimated, i.e. have a ratio below `1.0`
+    pub prediction_ratio: f64,
+    /// Whether the severity reached [`TivConfig::severity_threshold`]
+    pub flagged: bool,
+}
+
+/// The result of [`analyze`]
+#[derive(Debug, Clone)]
+pub struct TivReport {
+    /// Every distinct measured edge, sorted by `(a, b)`
+    pub edges: Vec<TivEdge>,
+    /// How TIV-prone each node is: the fraction of the node's measured edges
+    /// that were flagged, or `0.0` for nodes without any measured edges
+    pub node_scores: Vec<f64>,
+    flagged_weight: f64,
+}
+
+impl TivReport {
+    /// Returns an iterator over the flagged edges
+    pub fn flagged(&self) -> impl Iterator<Item = &TivEdge> {
+        self.edges.iter().filter(|e| e.flagged)
+    }
+
+    /// Returns the measured edge between `a` and `b`, given in either order
+    pub fn edge(&self, a: usize, b: usize) -> Option<&TivEdge> {
+        let key = if a <= b { (a, b) } else { (b, a) };
+        self.edges
+            .binary_search_by(|e| (e.a, e.b).cmp(&key))
+            .ok()
+            .map(|i| &self.edges[i])
+    }
+
+    /// Returns true if the edge between `a` and `b` was flagged
+    pub fn is_flagged(&self, a: usize, b: usize) -> bool {
+        self.edge(a, b).map_or(false, |e| e.flagged)
+    }
+
+    /// Returns the weight that updates between `a` and `b` should be given:
+    /// [`TivConfig::flagged_weight`] for flagged edges, `1.0` otherwise
+    pub fn weight(&self, a: usize, b: usize) -> f64 {
+        if self.is_flagged(a, b) {
+            self.flagged_weight
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Scores every measured edge for triangle inequality violations
+///
+/// `rtts` are the measured `(a, b, rtt)` edges, where `a` and `b` are indices
+/// into `coords`. Edges are undirected; if an edge is given more than once
+/// the lowest RTT is used.
+///
+/// This precomputes an `N×N` matrix of RTTs and checks every other node as a
+/// detour for each edge, so requires `O(N²)` memory and `O(E·N)` time.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - An edge refers to a node outside of `coords`
+///   ([`ErrorKind::InvalidLength`])
+/// - The coordinates have different dimensions
+///   ([`ErrorKind::DimensionMismatch`])
+/// - An RTT is not a positive finite number ([`ErrorKind::InvalidData`])
+/// - `cfg.flagged_weight` is not within `[0.0, 1.0]`
+///   ([`ErrorKind::InvalidWeight`])
+pub fn analyze<T: Vector>(
+    coords: &[Coord<T>],
+    rtts: &[(usize, usize, f64)],
+    cfg: &TivConfig,
+) -> Result<TivReport> {
+    if !(0.0..=1.0).contains(&cfg.flagged_weight) {
+        return Err(Error {
+            kind: ErrorKind::InvalidWeight,
+        });
+    }
+
+    let n = coords.len();
+    let mut measured = vec![f64::NAN; n * n];
+    for &(a, b, rtt) in rtts {
+        if a >= n || b >= n {
+            return Err(Error {
+                kind: ErrorKind::InvalidLength,
+            });
+        }
+        if !(rtt.is_finite() && rtt > 0.0) {
+            return Err(Error {
+                kind: ErrorKind::InvalidData,
+            });
+        }
+        // `f64::min` ignores the NaN of a missing entry
+        let m = f64::min(measured[a * n + b], rtt);
+        measured[a * n + b] = m;
+        measured[b * n + a] = m;
+    }
+
+    // Each detour leg is the measured RTT if there is one, the estimate if not
+    let mut legs = vec![0.0; n * n];
+    matrix::distance_matrix(coords, &mut legs)?;
+    for (l, m) in legs.iter_mut().zip(&measured) {
+        if !m.is_nan() {
+            *l = *m;
+        }
+    }
+
+    let mut edges = Vec::new();
+    let mut incident = vec![0usize; n];
+    let mut flagged = vec![0usize; n];
+    for a in 0..n {
+        for b in a + 1..n {
+            let rtt = measured[a * n + b];
+            if rtt.is_nan() {
+                continue;
+            }
+
+            let mut via = None;
+            let mut detour = f64::INFINITY;
+            for c in (0..n).filter(|c| *c != a && *c != b) {
+                let d = legs[a * n + c] + legs[c * n + b];
+                if d < detour {
+                    detour = d;
+                    via = Some(c);
+                }
+            }
+            let severity = if via.is_some() { rtt / detour } else { 0.0 };
+            let is_flagged = severity >= cfg.severity_threshold;
+
+            incident[a] += 1;
+            incident[b] += 1;
+            if is_flagged {
+                flagged[a] += 1;
+                flagged[b] += 1;
+            }
+
+            edges.push(TivEdge {
+                a,
+                b,
+                rtt,
+                severity,
+                via,
+                prediction_ratio: coords[a].distance_to(&coords[b]) / rtt,
+                flagged: is_flagged,
+            });
+        }
+    }
+
+    let node_scores = incident
+        .iter()
+        .zip(&flagged)
+        .map(|(i, f)| if *i > 0 { *f as f64 / *i as f64 } else { 0.0 })
+        .collect();
+
+    Ok(TivReport {
+        edges,
+        node_scores,
+        flagged_weight: cfg.flagged_weight,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{close, heapless::VecD};
+
+    type Rtts = Vec<(usize, usize, f64)>;
+
+    /// Nodes 0, 1, 2 and 3 are all 10ms apart except 0 to 1, which is 50ms
+    fn square() -> (Vec<Coord<VecD<2>>>, Rtts) {
+        let coords = vec![
+            Coord::from(VecD::from([0.0, 0.0])),
+            Coord::from(VecD::from([0.01, 0.0])),
+            Coord::from(VecD::from([0.0, 0.01])),
+            Coord::from(VecD::from([0.01, 0.01])),
+        ];
+        let rtts = vec![
+            (0, 1, 0.050),
+            (1, 0, 0.060),
+            (0, 2, 0.010),
+            (0, 3, 0.010),
+            (1, 2, 0.010),
+            (1, 3, 0.010),
+            (2, 3, 0.010),
+        ];
+        (coords, rtts)
+    }
+
+    #[test]
+    fn flags_violating_edge() {
+        let (coords, rtts) = square();
+        let report = analyze(&coords, &rtts, &TivConfig::default()).unwrap();
+        assert_eq!(report.edges.len(), 6);
+        assert_eq!(report.flagged().count(), 1);
+
+        let e = report.edge(1, 0).unwrap();
+        assert!(e.flagged);
+        assert_eq!(e.rtt, 0.050);
+        assert_eq!(e.severity, 2.5);
+        assert!(e.via == Some(2) || e.via == Some(3));
+        assert!(e.prediction_ratio < 1.0);
+
+        let e = report.edge(2, 3).unwrap();
+        assert!(!e.flagged);
+        assert!(e.severity <= 1.0);
+    }
+
+    #[test]
+    fn node_scores() {
+        let (coords, rtts) = square();
+        let report = analyze(&coords, &rtts, &TivConfig::default()).unwrap();
+        assert_eq!(report.node_scores, vec![1.0 / 3.0, 1.0 / 3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn weights() {
+        let (coords, rtts) = square();
+        let cfg = TivConfig {
+            flagged_weight: 0.25,
+            ..Default::default()
+        };
+        let report = analyze(&coords, &rtts, &cfg).unwrap();
+        assert_eq!(report.weight(0, 1), 0.25);
+        assert_eq!(report.weight(0, 2), 1.0);
+        // unmeasured edges are not flagged
+        assert_eq!(report.weight(0, 9), 1.0);
+    }
+
+    #[test]
+    fn falls_back_to_estimates() {
+        // Only the direct edge is measured, detours use the coordinates
+        let (coords, _) = square();
+        let report = analyze(&coords, &[(0, 3, 0.1)], &TivConfig::default()).unwrap();
+        let e = report.edge(0, 3).unwrap();
+        assert!(e.flagged);
+        // the estimated detour is 0.02
+        assert!(close(0.1 / e.severity, 0.02));
+    }
+
+    #[test]
+    fn invalid_input() {
+        let (coords, _) = square();
+        let cfg = TivConfig::default();
+        assert!(analyze(&coords, &[(0, 4, 0.1)], &cfg)
+            .unwrap_err()
+            .is_invalid_length());
+        assert!(analyze(&coords, &[(0, 1, -0.1)], &cfg)
+            .unwrap_err()
+            .is_invalid_data());
+        assert!(analyze(&coords, &[(0, 1, f64::NAN)], &cfg)
+            .unwrap_err()
+            .is_invalid_data());
+        let cfg = TivConfig {
+            flagged_weight: 2.0,
+            ..Default::default()
+        };
+        assert!(analyze(&coords, &[], &cfg).unwrap_err().is_invalid_weight());
+    }
+}
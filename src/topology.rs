@@ -0,0 +1,493 @@
+//! Synthetic Internet-like topologies for evaluating coordinates
+//!
+//! Each generator returns a ground truth [`RttMatrix`], in seconds, built from
+//! the caller supplied RNG so results are reproducible given a seeded RNG. The
+//! matrices are symmetric with a zero diagonal and can be fed to a
+//! [`Simulation`](crate::sim::Simulation) or compared against any set of
+//! [`Coord`](crate::Coord)s.
+//!
+//! - [`transit_stub`]: hosts in stub domains hanging off a transit core, with
+//!   every path routed through the hierarchy
+//! - [`datacenters`]: tight clusters of hosts in regions joined by a sparse set
+//!   of inter-region links
+//! - [`geometric`]: hosts scattered through Euclidean space, each behind an
+//!   access link, i.e. exactly the model Vivaldi with heights assumes
+//! - [`inject_violations`]: inflates a fraction of the RTTs of any of the above
+//!   so they violate the triangle inequality
+//!
+//! ```rust
+//! use rand::{rngs::StdRng, SeedableRng};
+//! use violin::topology::{self, DatacenterConfig};
+//!
+//! let mut rng = StdRng::seed_from_u64(1);
+//! let cfg = DatacenterConfig::default();
+//! let rtts = topology::datacenters(&cfg, &mut rng).unwrap();
+//! assert_eq!(rtts.len(), cfg.regions * cfg.hosts_per_region);
+//!
+//! // Hosts are numbered region by region
+//! let same_region = rtts.get(0, 1).unwrap();
+//! let other_region = rtts.get(0, cfg.hosts_per_region).unwrap();
+//! assert!(same_region < other_region);
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use rand::Rng;
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    matrix::RttMatrix,
+};
+
+/// Tunables for [`transit_stub`]
+#[derive(Debug, Copy, Clone)]
+pub struct TransitStubConfig {
+    /// The number of transit (core) routers
+    pub transits: usize,
+    /// The number of stub domains attached to each transit router
+    pub stubs_per_transit: usize,
+    /// The number of hosts in each stub domain
+    pub hosts_per_stub: usize,
+    /// Transit routers are placed uniformly in a square this many seconds
+    /// across, and the latency between two of them is their distance
+    pub core_diameter: f64,
+    /// The range of latencies between a stub domain and its transit router
+    pub stub_latency: (f64, f64),
+    /// The range of latencies between a host and its stub domain
+    pub host_latency: (f64, f64),
+}
+
+impl Default for TransitStubConfig {
+    fn default() -> Self {
+        Self {
+            transits: 4,
+            stubs_per_transit: 3,
+            hosts_per_stub: 5,
+            core_diameter: 0.1,
+            stub_latency: (0.002, 0.02),
+            host_latency: (0.0005, 0.005),
+        }
+    }
+}
+
+/// Tunables for [`datacenters`]
+#[derive(Debug, Copy, Clone)]
+pub struct DatacenterConfig {
+    /// The number of regions
+    pub regions: usize,
+    /// The number of hosts in each region
+    pub hosts_per_region: usize,
+    /// Regions are placed uniformly in a square this many seconds across
+    pub diameter: f64,
+    /// Each region links to this many of its nearest regions, on top of the
+    /// links of a minimum spanning tree that keeps every region reachable.
+    /// Traffic between regions follows the shortest path over these links.
+    pub links: usize,
+    /// The range of latencies between a host and its region's network
+    pub host_latency: (f64, f64),
+}
+
+impl Default for DatacenterConfig {
+    fn default() -> Self {
+        Self {
+            regions: 5,
+            hosts_per_region: 10,
+            diameter: 0.15,
+            links: 1,
+            host_latency: (0.0002, 0.001),
+        }
+    }
+}
+
+/// Tunables for [`geometric`]
+#[derive(Debug, Copy, Clone)]
+pub struct GeometricConfig {
+    /// The number of hosts
+    pub hosts: usize,
+    /// The number of dimensions of the space
+    pub dimensions: usize,
+    /// Hosts are placed uniformly in a cube this many seconds across
+    pub diameter: f64,
+    /// The range of access link latencies (heights)
+    pub height: (f64, f64),
+}
+
+impl Default for GeometricConfig {
+    fn default() -> Self {
+        Self {
+            hosts: 100,
+            dimensions: 2,
+            diameter: 0.2,
+            height: (0.001, 0.01),
+        }
+    }
+}
+
+/// Tunables for [`inject_violations`]
+#[derive(Debug, Copy, Clone)]
+pub struct ViolationConfig {
+    /// The fraction of host pairs to inflate
+    pub fraction: f64,
+    /// The range of factors inflated RTTs are multiplied by
+    pub inflation: (f64, f64),
+}
+
+impl Default for ViolationConfig {
+    fn default() -> Self {
+        Self {
+            fraction: 0.05,
+            inflation: (1.5, 3.0),
+        }
+    }
+}
+
+/// Generates a transit-stub topology
+///
+/// Hosts are numbered stub by stub, and stubs transit router by transit
+/// router, so host `i` is in stub `i / hosts_per_stub`. Hosts in the same stub
+/// reach each other through the stub; all other traffic goes up to the
+/// transit core and back down.
+///
+/// # Errors
+///
+/// Returns an error if any count is zero, or a latency is negative, NaN or
+/// Infinite, or a range's lower bound is above its upper bound
+pub fn transit_stub<R: Rng + ?Sized>(cfg: &TransitStubConfig, rng: &mut R) -> Result<RttMatrix> {
+    check(
+        cfg.transits > 0 && cfg.stubs_per_transit > 0 && cfg.hosts_per_stub > 0,
+        &[
+            (cfg.core_diameter, cfg.core_diameter),
+            cfg.stub_latency,
+            cfg.host_latency,
+        ],
+    )?;
+
+    let transits: Vec<_> = (0..cfg.transits)
+        .map(|_| point(rng, 2, cfg.core_diameter))
+        .collect();
+    let stubs = cfg.transits * cfg.stubs_per_transit;
+    let stub_latency: Vec<_> = (0..stubs).map(|_| uniform(rng, cfg.stub_latency)).collect();
+    let n = stubs * cfg.hosts_per_stub;
+    let host_latency: Vec<_> = (0..n).map(|_| uniform(rng, cfg.host_latency)).collect();
+
+    let stub = |i: usize| i / cfg.hosts_per_stub;
+    let transit = |s: usize| s / cfg.stubs_per_transit;
+    Ok(RttMatrix::from_fn(n, |i, j| {
+        let (i, j) = ordered(i, j);
+        let (si, sj) = (stub(i), stub(j));
+        let mut rtt = host_latency[i] + host_latency[j];
+        if si != sj {
+            rtt += stub_latency[si]
+                + stub_latency[sj]
+                + distance(&transits[transit(si)], &transits[transit(sj)]);
+        }
+        Some(rtt)
+    }))
+}
+
+/// Generates clustered datacenters joined by inter-region links
+///
+/// Hosts are numbered region by region, so host `i` is in region
+/// `i / hosts_per_region`.
+///
+/// # Errors
+///
+/// Returns an error if `regions` or `hosts_per_region` is zero, or a latency
+/// is negative, NaN or Infinite, or a range's lower bound is above its upper
+/// bound
+pub fn datacenters<R: Rng + ?Sized>(cfg: &DatacenterConfig, rng: &mut R) -> Result<RttMatrix> {
+    check(
+        cfg.regions > 0 && cfg.hosts_per_region > 0,
+        &[(cfg.diameter, cfg.diameter), cfg.host_latency],
+    )?;
+
+    let r = cfg.regions;
+    let regions: Vec<_> = (0..r).map(|_| point(rng, 2, cfg.diameter)).collect();
+    let dist = |a: usize, b: usize| distance(&regions[a], &regions[b]);
+
+    // paths[a * r + b] is the shortest path from region a to b, starting with
+    // only the links
+    let mut paths = vec![f64::INFINITY; r * r];
+    let link = |paths: &mut Vec<f64>, a: usize, b: usize| {
+        paths[a * r + b] = dist(a, b);
+        paths[b * r + a] = dist(a, b);
+    };
+    for a in 0..r {
+        paths[a * r + a] = 0.0;
+        let mut nearest: Vec<_> = (0..r).filter(|b| *b != a).collect();
+        nearest.sort_by(|x, y| dist(a, *x).partial_cmp(&dist(a, *y)).unwrap());
+        for &b in nearest.iter().take(cfg.links) {
+            link(&mut paths, a, b);
+        }
+    }
+    // Prim's algorithm
+    let mut in_tree = vec![false; r];
+    in_tree[0] = true;
+    for _ in 1..r {
+        let mut best = None;
+        for a in (0..r).filter(|a| in_tree[*a]) {
+            for b in (0..r).filter(|b| !in_tree[*b]) {
+                if best.map_or(true, |(_, _, d)| dist(a, b) < d) {
+                    best = Some((a, b, dist(a, b)));
+                }
+            }
+        }
+        // There is always a region left outside the tree
+        let (a, b, _) = best.unwrap();
+        in_tree[b] = true;
+        link(&mut paths, a, b);
+    }
+    // Floyd-Warshall
+    for k in 0..r {
+        for a in 0..r {
+            for b in 0..r {
+                let via = paths[a * r + k] + paths[k * r + b];
+                if via < paths[a * r + b] {
+                    paths[a * r + b] = via;
+                }
+            }
+        }
+    }
+
+    let n = r * cfg.hosts_per_region;
+    let host_latency: Vec<_> = (0..n).map(|_| uniform(rng, cfg.host_latency)).collect();
+    let region = |i: usize| i / cfg.hosts_per_region;
+    Ok(RttMatrix::from_fn(n, |i, j| {
+        let (i, j) = ordered(i, j);
+        Some(host_latency[i] + host_latency[j] + paths[region(i) * r + region(j)])
+    }))
+}
+
+/// Generates hosts scattered uniformly through Euclidean space, each with an
+/// access link
+///
+/// The RTT between two hosts is the distance between them plus both of their
+/// access link latencies.
+///
+/// # Errors
+///
+/// Returns an error if `hosts` or `dimensions` is zero, or a latency is
+/// negative, NaN or Infinite, or a range's lower bound is above its upper
+/// bound
+pub fn geometric<R: Rng + ?Sized>(cfg: &GeometricConfig, rng: &mut R) -> Result<RttMatrix> {
+    check(
+        cfg.hosts > 0 && cfg.dimensions > 0,
+        &[(cfg.diameter, cfg.diameter), cfg.height],
+    )?;
+
+    let hosts: Vec<_> = (0..cfg.hosts)
+        .map(|_| {
+            (
+                point(rng, cfg.dimensions, cfg.diameter),
+                uniform(rng, cfg.height),
+            )
+        })
+        .collect();
+    Ok(RttMatrix::from_fn(cfg.hosts, |i, j| {
+        let (i, j) = ordered(i, j);
+        let ((a, ha), (b, hb)) = (&hosts[i], &hosts[j]);
+        Some(distance(a, b) + ha + hb)
+    }))
+}
+
+/// Multiplies the RTTs of a random `fraction` of host pairs by a factor within
+/// `inflation`, in both directions, returning the `(a, b)` pairs with `a < b`
+/// that were inflated
+///
+/// Only pairs with an RTT in both directions are considered. In any topology
+/// where other paths exist, inflating an RTT by more than the detour factor
+/// makes that pair violate the triangle inequality.
+///
+/// # Errors
+///
+/// Returns an error if `fraction` is not within `[0.0, 1.0]`, or the inflation
+/// range is NaN, Infinite, below `1.0` or its lower bound is above its upper
+/// bound
+pub fn inject_violations<R: Rng + ?Sized>(
+    rtts: &mut RttMatrix,
+    cfg: &ViolationConfig,
+    rng: &mut R,
+) -> Result<Vec<(usize, usize)>> {
+    let (lo, hi) = cfg.inflation;
+    check(
+        (0.0..=1.0).contains(&cfg.fraction) && lo >= 1.0,
+        &[cfg.inflation],
+    )?;
+
+    let mut inflated = Vec::new();
+    for a in 0..rtts.len() {
+        for b in a + 1..rtts.len() {
+            if let (Some(ab), Some(ba)) = (rtts.get(a, b), rtts.get(b, a)) {
+                if rng.gen_bool(cfg.fraction) {
+                    let f = uniform(rng, (lo, hi));
+                    rtts.set(a, b, ab * f);
+                    rtts.set(b, a, ba * f);
+                    inflated.push((a, b));
+                }
+            }
+        }
+    }
+    Ok(inflated)
+}
+
+/// Returns an error unless `ok` holds and every range is finite, non-negative
+/// and ordered
+fn check(ok: bool, ranges: &[(f64, f64)]) -> Result<()> {
+    let valid = |(lo, hi): &(f64, f64)| lo.is_finite() && hi.is_finite() && 0.0 <= *lo && lo <= hi;
+    if ok && ranges.iter().all(valid) {
+        Ok(())
+    } else {
+        Err(Error {
+            kind: ErrorKind::InvalidConfig,
+        })
+    }
+}
+
+/// Orders a pair so both directions are computed identically, keeping the
+/// matrix exactly symmetric despite rounding
+fn ordered(i: usize, j: usize) -> (usize, usize) { (i.min(j), i.max(j)) }
+
+fn uniform<R: Rng + ?Sized>(rng: &mut R, (lo, hi): (f64, f64)) -> f64 {
+    if hi > lo {
+        rng.gen_range(lo..hi)
+    } else {
+        lo
+    }
+}
+
+fn point<R: Rng + ?Sized>(rng: &mut R, dimensions: usize, diameter: f64) -> Vec<f64> {
+    (0..dimensions)
+        .map(|_| uniform(rng, (0.0, diameter)))
+        .collect()
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    crate::sqrt(a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn rng() -> StdRng { StdRng::seed_from_u64(9) }
+
+    fn assert_symmetric(m: &RttMatrix) {
+        for i in 0..m.len() {
+            assert_eq!(m.get(i, i), Some(0.0));
+            for j in 0..m.len() {
+                assert_eq!(m.get(i, j), m.get(j, i));
+                assert!(m.get(i, j).unwrap() >= 0.0);
+            }
+        }
+    }
+
+    /// Returns the largest ratio of an RTT to its shortest two-hop detour
+    fn worst_violation(m: &RttMatrix) -> f64 {
+        let n = m.len();
+        let mut worst: f64 = 0.0;
+        for a in 0..n {
+            for b in a + 1..n {
+                let detour = (0..n)
+                    .filter(|c| *c != a && *c != b)
+                    .map(|c| m.get(a, c).unwrap() + m.get(c, b).unwrap())
+                    .fold(f64::INFINITY, f64::min);
+                worst = worst.max(m.get(a, b).unwrap() / detour);
+            }
+        }
+        worst
+    }
+
+    #[test]
+    fn transit_stub_hierarchy() {
+        let cfg = TransitStubConfig::default();
+        let m = transit_stub(&cfg, &mut rng()).unwrap();
+        assert_eq!(m.len(), 60);
+        assert_symmetric(&m);
+        // Within a stub is at most two host links
+        assert!(m.get(0, 1).unwrap() <= 2.0 * cfg.host_latency.1);
+        // Leaving a stub adds at least two stub links
+        assert!(m.get(0, 5).unwrap() >= 2.0 * cfg.stub_latency.0);
+    }
+
+    #[test]
+    fn datacenter_regions() {
+        let cfg = DatacenterConfig::default();
+        let m = datacenters(&cfg, &mut rng()).unwrap();
+        assert_eq!(m.len(), 50);
+        assert_symmetric(&m);
+        for i in 0..50 {
+            for j in 0..50 {
+                let same = i / 10 == j / 10;
+                assert_eq!(m.get(i, j).unwrap() <= 2.0 * cfg.host_latency.1, same);
+            }
+        }
+        // Shortest paths over links still satisfy the triangle inequality
+        assert!(worst_violation(&m) <= 1.0 + 1.0e-9);
+    }
+
+    #[test]
+    fn geometric_is_metric() {
+        let cfg = GeometricConfig {
+            hosts: 30,
+            dimensions: 3,
+            ..Default::default()
+        };
+        let m = geometric(&cfg, &mut rng()).unwrap();
+        assert_eq!(m.len(), 30);
+        assert_symmetric(&m);
+        assert!(worst_violation(&m) <= 1.0 + 1.0e-9);
+    }
+
+    #[test]
+    fn violations() {
+        let mut m = geometric(&GeometricConfig::default(), &mut rng()).unwrap();
+        let before = m.clone();
+        let cfg = ViolationConfig {
+            fraction: 0.1,
+            inflation: (3.0, 4.0),
+        };
+        let inflated = inject_violations(&mut m, &cfg, &mut rng()).unwrap();
+        assert!(!inflated.is_empty());
+        assert_symmetric(&m);
+        for &(a, b) in &inflated {
+            let f = m.get(a, b).unwrap() / before.get(a, b).unwrap();
+            assert!((3.0..=4.0).contains(&f));
+        }
+        assert!(worst_violation(&m) > 1.5);
+    }
+
+    #[test]
+    fn reproducible() {
+        let cfg = TransitStubConfig::default();
+        let a = transit_stub(&cfg, &mut rng()).unwrap();
+        let b = transit_stub(&cfg, &mut rng()).unwrap();
+        let c = transit_stub(&cfg, &mut StdRng::seed_from_u64(10)).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let cfg = GeometricConfig {
+            hosts: 0,
+            ..Default::default()
+        };
+        assert!(geometric(&cfg, &mut rng()).unwrap_err().is_invalid_config());
+        let cfg = DatacenterConfig {
+            host_latency: (0.01, 0.001),
+            ..Default::default()
+        };
+        assert!(datacenters(&cfg, &mut rng())
+            .unwrap_err()
+            .is_invalid_config());
+        let cfg = ViolationConfig {
+            inflation: (0.5, 2.0),
+            ..Default::default()
+        };
+        assert!(inject_violations(&mut RttMatrix::new(2), &cfg, &mut rng())
+            .unwrap_err()
+            .is_invalid_config());
+    }
+}
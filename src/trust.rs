@@ -0,0 +1,186 @@
+//! Per-peer trust weights for updates
+//!
+//! Not every peer deserves the same say over a node's coordinate. A
+//! [`TrustStore`] keeps a weight within `[0.0, 1.0]` for each peer ID, which
+//! [`Node::update_trusted`](crate::Node::update_trusted) uses to scale the
+//! impact of that peer's samples on both the coordinate and the error
+//! estimate, exactly as [`Node::update_weighted`](crate::Node::update_weighted)
+//! does for a single sample. Peers without their own weight get the store's
+//! default.
+//!
+//! Weights can be adjusted over time, e.g. lowering a peer's trust when a
+//! [`Policy`](crate::Policy) rejects its samples, and letting it recover
+//! toward the default with [`TrustStore::relax`].
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use violin::{heapless::VecD, trust::TrustStore, Coord, Node};
+//!
+//! // customer run edge agents get half the say of internal nodes by default
+//! let mut trust = TrustStore::new(0.5).unwrap();
+//! trust.set("internal-1", 1.0).unwrap();
+//! trust.adjust("edge-7", -0.5);
+//! assert_eq!(trust.get(&"edge-7"), 0.0);
+//!
+//! let mut node = Node::<VecD<2>>::new();
+//! let edge = Coord::from(VecD::from([0.5, 0.0]));
+//! node.update_trusted(&trust, &"edge-7", Duration::from_millis(50), &edge);
+//! assert_eq!(node.coordinate().raw_coord(), &VecD::default());
+//! ```
+
+use alloc::collections::BTreeMap;
+
+use crate::error::{Error, ErrorKind, Result};
+
+/// The trust weights of peers identified by `K`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustStore<K> {
+    default: f64,
+    weights: BTreeMap<K, f64>,
+}
+
+impl<K: Ord> Default for TrustStore<K> {
+    /// Fully trusts every peer
+    fn default() -> Self {
+        Self {
+            default: 1.0,
+            weights: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Ord> TrustStore<K> {
+    /// Creates a store where every peer has the `default` weight
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `default` is not within `[0.0, 1.0]`
+    pub fn new(default: f64) -> Result<Self> {
+        check(default)?;
+        Ok(Self {
+            default,
+            weights: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the weight of peers without their own
+    pub fn default_weight(&self) -> f64 { self.default }
+
+    /// Returns the weight of `peer`
+    pub fn get(&self, peer: &K) -> f64 { self.weights.get(peer).copied().unwrap_or(self.default) }
+
+    /// Sets the weight of `peer`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `weight` is not within `[0.0, 1.0]`
+    pub fn set(&mut self, peer: K, weight: f64) -> Result<()> {
+        check(weight)?;
+        self.weights.insert(peer, weight);
+        Ok(())
+    }
+
+    /// Adds `delta` to the weight of `peer`, clamped to `[0.0, 1.0]`, and
+    /// returns the new weight. A NaN `delta` leaves the weight unchanged.
+    pub fn adjust(&mut self, peer: K, delta: f64) -> f64 {
+        let default = self.default;
+        let w = self.weights.entry(peer).or_insert(default);
+        if !delta.is_nan() {
+            *w = (*w + delta).clamp(0.0, 1.0);
+        }
+        *w
+    }
+
+    /// Moves every peer's weight toward the default by `rate`, where `0.0`
+    /// changes nothing and `1.0` resets every peer to the default. Peers that
+    /// reach the default are forgotten.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `rate` is not within `[0.0, 1.0]`
+    pub fn relax(&mut self, rate: f64) -> Result<()> {
+        check(rate)?;
+        let default = self.default;
+        for w in self.weights.values_mut() {
+            *w += (default - *w) * rate;
+        }
+        self.weights.retain(|_, w| *w != default);
+        Ok(())
+    }
+
+    /// Forgets the weight of `peer`, returning it to the default
+    pub fn remove(&mut self, peer: &K) -> Option<f64> { self.weights.remove(peer) }
+
+    /// Returns the peers with their own weight along with that weight
+    pub fn iter(&self) -> impl Iterator<Item = (&K, f64)> {
+        self.weights.iter().map(|(k, w)| (k, *w))
+    }
+}
+
+fn check(weight: f64) -> Result<()> {
+    if (0.0..=1.0).contains(&weight) {
+        return Ok(());
+    }
+    Err(Error {
+        kind: ErrorKind::InvalidWeight,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{heapless::VecD, std::time::Duration, Coord, Node, Vector};
+
+    #[test]
+    fn weights() {
+        let mut t = TrustStore::new(0.8).unwrap();
+        assert_eq!(t.get(&1), 0.8);
+        t.set(1, 0.2).unwrap();
+        assert_eq!(t.get(&1), 0.2);
+        assert!(t.set(2, 1.5).unwrap_err().is_invalid_weight());
+        assert!(TrustStore::<u8>::new(-0.1).unwrap_err().is_invalid_weight());
+
+        assert_eq!(t.adjust(1, -1.0), 0.0);
+        assert_eq!(t.adjust(3, 0.5), 1.0);
+        assert_eq!(t.adjust(3, f64::NAN), 1.0);
+        assert_eq!(t.iter().collect::<Vec<_>>(), [(&1, 0.0), (&3, 1.0)]);
+
+        t.relax(0.5).unwrap();
+        assert_eq!(t.get(&1), 0.4);
+        assert_eq!(t.get(&3), 0.9);
+        t.relax(1.0).unwrap();
+        assert_eq!(t.iter().count(), 0);
+        assert!(t.relax(2.0).unwrap_err().is_invalid_weight());
+
+        assert_eq!(t.remove(&1), None);
+    }
+
+    #[test]
+    fn node_updates() {
+        let mut trust = TrustStore::default();
+        trust.set("edge", 0.25).unwrap();
+        let other = Coord::from(VecD::from([0.1, 0.0]));
+        let rtt = Duration::from_millis(300);
+
+        let mut trusted = Node::<VecD<2>>::new();
+        let mut weighted = trusted.clone();
+        let mut edge = trusted.clone();
+        trusted
+            .try_update_trusted(&trust, &"core", rtt, &other)
+            .unwrap();
+        weighted.try_update_weighted(rtt, &other, 0.25).unwrap();
+        assert!(edge.update_trusted(&trust, &"edge", rtt, &other));
+
+        assert_eq!(
+            edge.coordinate().raw_coord(),
+            weighted.coordinate().raw_coord()
+        );
+        assert!(
+            edge.coordinate().raw_coord().magnitude()
+                < trusted.coordinate().raw_coord().magnitude()
+        );
+    }
+}
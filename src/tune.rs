@@ -0,0 +1,786 @@
+//! Searching for the [`Config`] that works best on a given network
+//!
+//! [`tune`] runs a [`Simulation`] of the same scenario once per candidate
+//! config and ranks the candidates by an [`Objective`]. The scenario is a
+//! ground truth [`RttMatrix`], e.g. a public dataset from
+//! [`dataset`](crate::dataset) or a synthetic one from
+//! [`topology`](crate::topology), along with the [`SimConfig`] describing probe
+//! rates, noise, loss and churn.
+//!
+//! Candidates come from a grid or uniformly at random within a [`Space`].
+//! Every candidate's simulation is seeded identically, so differences in score
+//! come from the config alone, and a whole search is reproducible given a
+//! seeded RNG.
+//!
+//! The resulting [`Tuning`] can be written as a Markdown table or CSV to check
+//! in alongside the chosen config.
+//!
+//! [`compare`] answers the other tuning question, how many dimensions to use
+//! and whether heights and an adjustment window pay off, by running the same
+//! scenario through each combination and scoring the final coordinates.
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use rand::{rngs::StdRng, SeedableRng};
+//! use violin::{
+//!     heapless::VecD,
+//!     sim::SimConfig,
+//!     topology::{self, GeometricConfig},
+//!     tune::{self, Objective, Search, Space},
+//! };
+//!
+//! let mut rng = StdRng::seed_from_u64(3);
+//! let truth = topology::geometric(
+//!     &GeometricConfig {
+//!         hosts: 20,
+//!         ..Default::default()
+//!     },
+//!     &mut rng,
+//! )
+//! .unwrap();
+//! let scenario = SimConfig {
+//!     duration: Duration::from_secs(60),
+//!     ..Default::default()
+//! };
+//!
+//! let results = tune::tune::<VecD<2>, _>(
+//!     &truth,
+//!     &scenario,
+//!     &Space::default(),
+//!     Search::Random { trials: 4 },
+//!     Objective::MedianError,
+//!     &mut rng,
+//! )
+//! .unwrap();
+//! let best = results.best().cfg;
+//!
+//! let mut table = String::new();
+//! results.write_markdown(&mut table).unwrap();
+//! assert_eq!(table.lines().count(), 2 + 4);
+//! ```
+
+use alloc::vec::Vec;
+
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    error::{Error, ErrorKind, Result},
+    heapless::{VecD, Window},
+    matrix::RttMatrix,
+    metrics::{self, Accuracy},
+    sim::{SimConfig, Simulation},
+    std::fmt,
+    window, Config, Vector,
+};
+
+/// The ranges of [`Config`] values to search, each as `(low, high)`
+/// inclusive. A range with `low == high` fixes that value.
+#[derive(Debug, Copy, Clone)]
+pub struct Space {
+    /// See [`Config::cc`]
+    pub cc: (f64, f64),
+    /// See [`Config::ce`]
+    pub ce: (f64, f64),
+    /// See [`Config::gravity_rho`]
+    pub gravity_rho: (f64, f64),
+    /// See [`Config::error_max`]
+    pub error_max: (f64, f64),
+    /// See [`Config::height_min`]
+    pub height_min: (f64, f64),
+}
+
+impl Default for Space {
+    /// Searches `cc`, `ce` and `gravity_rho` around their defaults, keeping
+    /// `error_max` and `height_min` at theirs
+    fn default() -> Self {
+        let cfg = Config::default();
+        Self {
+            cc: (0.05, 0.5),
+            ce: (0.05, 0.5),
+            gravity_rho: (50.0, 500.0),
+            error_max: (cfg.error_max, cfg.error_max),
+            height_min: (cfg.height_min, cfg.height_min),
+        }
+    }
+}
+
+/// How [`tune`] picks candidates from a [`Space`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Search {
+    /// Every combination of `steps` evenly spaced values (including both
+    /// ends) of each range. Fixed ranges contribute a single value, so this
+    /// runs up to `steps⁵` simulations.
+    Grid {
+        /// The number of values per range
+        steps: usize,
+    },
+    /// `trials` candidates drawn uniformly from each range
+    Random {
+        /// The number of candidates
+        trials: usize,
+    },
+}
+
+/// What [`tune`] minimizes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Objective {
+    /// The median relative error at the end of the run
+    MedianError,
+    /// The 90th percentile relative error at the end of the run
+    P90Error,
+    /// Coordinate movement per second over the second half of the run
+    Stability,
+}
+
+/// A single evaluated candidate
+#[derive(Debug, Copy, Clone)]
+pub struct Trial {
+    /// The candidate config
+    pub cfg: Config,
+    /// The value of the [`Objective`] the trials were ranked by
+    pub score: f64,
+    /// The median relative error at the end of the run
+    pub median_error: f64,
+    /// The 90th percentile relative error at the end of the run
+    pub p90_error: f64,
+    /// The mean coordinate movement per second over the second half of the
+    /// run, see [`metrics::stability`]
+    pub stability: f64,
+}
+
+/// The result of [`tune`]
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    /// Every trial, best first
+    pub trials: Vec<Trial>,
+}
+
+impl Tuning {
+    /// Returns the best trial
+    pub fn best(&self) -> &Trial { &self.trials[0] }
+
+    /// Writes the trials as CSV, best first
+    pub fn write_csv<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(
+            w,
+            "cc,ce,gravity_rho,error_max,height_min,score,median_error,p90_error,stability"
+        )?;
+        for t in &self.trials {
+            let c = &t.cfg;
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{}",
+                c.cc,
+                c.ce,
+                c.gravity_rho,
+                c.error_max,
+                c.height_min,
+                t.score,
+                t.median_error,
+                t.p90_error,
+                t.stability
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the trials as a Markdown table, best first
+    pub fn write_markdown<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(
+            w,
+            "| cc | ce | gravity_rho | error_max | height_min | score | median error | p90 error | stability |"
+        )?;
+        writeln!(w, "|---|---|---|---|---|---|---|---|---|")?;
+        for t in &self.trials {
+            let c = &t.cfg;
+            writeln!(
+                w,
+                "| {:.4} | {:.4} | {:.1} | {:.4} | {:.6} | {:.6} | {:.4} | {:.4} | {:.6} |",
+                c.cc,
+                c.ce,
+                c.gravity_rho,
+                c.error_max,
+                c.height_min,
+                t.score,
+                t.median_error,
+                t.p90_error,
+                t.stability
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Simulates `scenario` on `truth` once per candidate config and ranks them by
+/// `objective`
+///
+/// Each candidate takes the searched values from `space` and every other field
+/// from [`SimConfig::node`], and [`SimConfig::snapshots`] is always enabled.
+/// `V` is the coordinate vector the nodes use. Nodes have no adjustment
+/// window, see [`compare`] for whether one pays off.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - A range is NaN, Infinite or its low end is above its high end
+/// - `cc` or `ce` is not within `(0.0, 1.0]`, `gravity_rho` or `error_max` is
+///   not positive, or `height_min` is negative
+/// - The search has zero steps or trials
+/// - The scenario is invalid (see [`Simulation::new`])
+/// - The ground truth has no known RTTs between distinct nodes
+pub fn tune<V, R>(
+    truth: &RttMatrix,
+    scenario: &SimConfig,
+    space: &Space,
+    search: Search,
+    objective: Objective,
+    rng: &mut R,
+) -> Result<Tuning>
+where
+    V: Vector + Clone,
+    R: Rng + SeedableRng,
+{
+    let valid = |(lo, hi): (f64, f64), min: f64, max: f64, open: bool| {
+        lo.is_finite()
+            && hi.is_finite()
+            && lo <= hi
+            && (lo > min || (!open && lo == min))
+            && hi <= max
+    };
+    let ok = valid(space.cc, 0.0, 1.0, true)
+        && valid(space.ce, 0.0, 1.0, true)
+        && valid(space.gravity_rho, 0.0, f64::MAX, true)
+        && valid(space.error_max, 0.0, f64::MAX, true)
+        && valid(space.height_min, 0.0, f64::MAX, false)
+        && !matches!(
+            search,
+            Search::Grid { steps: 0 } | Search::Random { trials: 0 }
+        );
+    if !ok {
+        return Err(Error {
+            kind: ErrorKind::InvalidConfig,
+        });
+    }
+
+    let candidates = match search {
+        Search::Grid { steps } => grid(space, steps, &scenario.node),
+        Search::Random { trials } => (0..trials)
+            .map(|_| {
+                let mut u =
+                    |(lo, hi): (f64, f64)| if hi > lo { rng.gen_range(lo..=hi) } else { lo };
+                Config {
+                    cc: u(space.cc),
+                    ce: u(space.ce),
+                    gravity_rho: u(space.gravity_rho),
+                    error_max: u(space.error_max),
+                    height_min: u(space.height_min),
+                    ..scenario.node
+                }
+            })
+            .collect(),
+    };
+
+    // Common random numbers: every candidate sees the same probes, loss and
+    // churn
+    let seed = rng.gen::<u64>();
+    let mut trials = candidates
+        .into_iter()
+        .map(|cfg| evaluate::<V, R>(truth, scenario, cfg, objective, seed))
+        .collect::<Result<Vec<_>>>()?;
+    trials.sort_by(|a, b| {
+        a.score
+            .partial_cmp(&b.score)
+            .unwrap_or_else(|| a.score.is_nan().cmp(&b.score.is_nan()))
+    });
+
+    Ok(Tuning { trials })
+}
+
+fn grid(space: &Space, steps: usize, base: &Config) -> Vec<Config> {
+    let values = |(lo, hi): (f64, f64)| -> Vec<f64> {
+        if hi > lo && steps > 1 {
+            (0..steps)
+                .map(|i| lo + (hi - lo) * i as f64 / (steps - 1) as f64)
+                .collect()
+        } else {
+            alloc::vec![lo]
+        }
+    };
+
+    let mut out = Vec::new();
+    for cc in values(space.cc) {
+        for ce in values(space.ce) {
+            for gravity_rho in values(space.gravity_rho) {
+                for error_max in values(space.error_max) {
+                    for height_min in values(space.height_min) {
+                        out.push(Config {
+                            cc,
+                            ce,
+                            gravity_rho,
+                            error_max,
+                            height_min,
+                            ..*base
+                        });
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+fn evaluate<V, R>(
+    truth: &RttMatrix,
+    scenario: &SimConfig,
+    cfg: Config,
+    objective: Objective,
+    seed: u64,
+) -> Result<Trial>
+where
+    V: Vector + Clone,
+    R: Rng + SeedableRng,
+{
+    let sim_cfg = SimConfig {
+        node: cfg,
+        snapshots: true,
+        ..*scenario
+    };
+    let mut sim = Simulation::<R, V>::new(truth, sim_cfg, R::seed_from_u64(seed))?;
+    sim.run();
+
+    let coords: Vec<_> = sim.nodes().iter().map(|n| n.coordinate().clone()).collect();
+    let error = metrics::relative_error(truth, &coords)?;
+
+    let samples = sim.samples();
+    let late = &samples[samples.len() / 2..];
+    let movement = metrics::stability(late.iter().map(|s| (s.time, &s.coords[..])))?;
+    let stability = if movement.is_empty() {
+        0.0
+    } else {
+        movement.iter().sum::<f64>() / movement.len() as f64
+    };
+
+    Ok(Trial {
+        cfg,
+        score: match objective {
+            Objective::MedianError => error.median,
+            Objective::P90Error => error.p90,
+            Objective::Stability => stability,
+        },
+        median_error: error.median,
+        p90_error: error.p90,
+        stability,
+    })
+}
+
+/// The largest number of dimensions [`compare`] supports
+pub const MAX_DIMENSIONS: usize = 16;
+
+/// The length of the adjustment window [`compare`] uses for variants with one
+pub const WINDOW: usize = 16;
+
+/// The accuracy of one combination of coordinate options, see [`compare`]
+#[derive(Debug, Copy, Clone)]
+pub struct Variant {
+    /// The number of dimensions of the coordinate vector
+    pub dimensions: usize,
+    /// Whether coordinates had a height
+    pub height: bool,
+    /// Whether nodes had an adjustment window of [`WINDOW`] samples
+    pub window: bool,
+    /// The accuracy of the coordinates at the end of the run
+    pub accuracy: Accuracy,
+}
+
+/// The result of [`compare`]
+#[derive(Debug, Clone)]
+pub struct Comparison {
+    /// Every variant, by dimensions, then without and with height, then
+    /// without and with a window
+    pub variants: Vec<Variant>,
+}
+
+impl Comparison {
+    /// Returns the variant with the lowest median relative error
+    pub fn best(&self) -> &Variant {
+        self.variants.iter().fold(&self.variants[0], |best, v| {
+            if v.accuracy.relative.median < best.accuracy.relative.median {
+                v
+            } else {
+                best
+            }
+        })
+    }
+
+    /// Writes the variants as CSV
+    pub fn write_csv<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(
+            w,
+            "dimensions,height,window,median_error,p90_error,median_absolute_error,rank_loss,closest_neighbor_loss"
+        )?;
+        for v in &self.variants {
+            let a = &v.accuracy;
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{}",
+                v.dimensions,
+                v.height,
+                v.window,
+                a.relative.median,
+                a.relative.p90,
+                a.absolute.median,
+                a.rank_loss.mean,
+                a.closest_neighbor_loss.mean
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes the variants as a Markdown table
+    pub fn write_markdown<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(
+            w,
+            "| dimensions | height | window | median error | p90 error | median absolute error | rank loss | closest neighbor loss |"
+        )?;
+        writeln!(w, "|---|---|---|---|---|---|---|---|")?;
+        for v in &self.variants {
+            let a = &v.accuracy;
+            let yes = |b: bool| if b { "yes" } else { "no" };
+            writeln!(
+                w,
+                "| {} | {} | {} | {:.4} | {:.4} | {:.6} | {:.4} | {:.6} |",
+                v.dimensions,
+                yes(v.height),
+                yes(v.window),
+                a.relative.median,
+                a.relative.p90,
+                a.absolute.median,
+                a.rank_loss.mean,
+                a.closest_neighbor_loss.mean
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Simulates `scenario` on `truth` with every number of `dimensions`, each
+/// without and with a height and without and with an adjustment window, and
+/// measures the [`metrics::accuracy`] of the final coordinates
+///
+/// Coordinates use [`heapless::VecD`](crate::heapless::VecD). Heights only
+/// grow from a non-zero floor, so variants without a height set
+/// [`Config::height_min`] to `0.0` and variants with one use `height_min`.
+/// Every simulation is seeded identically.
+///
+/// # Errors
+///
+/// Returns an error if any:
+///
+/// - `dimensions` is empty or has a value of `0` or above [`MAX_DIMENSIONS`]
+/// - `height_min` is not positive and finite
+/// - The scenario is invalid (see [`Simulation::new`])
+/// - The ground truth does not have enough known RTTs (see
+///   [`metrics::accuracy`])
+pub fn compare<R>(
+    truth: &RttMatrix,
+    scenario: &SimConfig,
+    dimensions: &[usize],
+    height_min: f64,
+    rng: &mut R,
+) -> Result<Comparison>
+where
+    R: Rng + SeedableRng,
+{
+    if dimensions.is_empty()
+        || dimensions.iter().any(|&d| d == 0 || d > MAX_DIMENSIONS)
+        || !(height_min.is_finite() && height_min > 0.0)
+    {
+        return Err(Error {
+            kind: ErrorKind::InvalidConfig,
+        });
+    }
+
+    let seed = rng.gen::<u64>();
+    let mut variants = Vec::new();
+    for &d in dimensions {
+        for height in [false, true] {
+            for window in [false, true] {
+                let scenario = SimConfig {
+                    node: Config {
+                        height_min: if height { height_min } else { 0.0 },
+                        ..scenario.node
+                    },
+                    ..*scenario
+                };
+                variants.push(Variant {
+                    dimensions: d,
+                    height,
+                    window,
+                    accuracy: if window {
+                        dispatch::<Window<WINDOW>, R>(truth, &scenario, d, seed)?
+                    } else {
+                        dispatch::<Window<0>, R>(truth, &scenario, d, seed)?
+                    },
+                });
+            }
+        }
+    }
+
+    Ok(Comparison { variants })
+}
+
+/// Picks the coordinate vector type for `d` dimensions
+fn dispatch<A, R>(truth: &RttMatrix, scenario: &SimConfig, d: usize, seed: u64) -> Result<Accuracy>
+where
+    A: window::Window,
+    R: Rng + SeedableRng,
+{
+    macro_rules! dimensions {
+        ($($n:literal)*) => {
+            match d {
+                $($n => accuracy::<VecD<$n>, A, R>(truth, scenario, seed),)*
+                _ => unreachable!(),
+            }
+        };
+    }
+    dimensions!(1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16)
+}
+
+fn accuracy<V, A, R>(truth: &RttMatrix, scenario: &SimConfig, seed: u64) -> Result<Accuracy>
+where
+    V: Vector + Clone,
+    A: window::Window,
+    R: Rng + SeedableRng,
+{
+    let mut sim = Simulation::<R, V, A>::new(truth, *scenario, R::seed_from_u64(seed))?;
+    sim.run();
+    let coords: Vec<_> = sim.nodes().iter().map(|n| n.coordinate().clone()).collect();
+    metrics::accuracy(truth, &coords)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::{std::time::Duration, Coord};
+
+    fn truth() -> RttMatrix {
+        let coords: Vec<_> = (0..16)
+            .map(|i| Coord::from(VecD::from([(i % 4) as f64 * 0.02, (i / 4) as f64 * 0.02])))
+            .collect();
+        RttMatrix::from_coords(&coords)
+    }
+
+    fn scenario() -> SimConfig {
+        SimConfig {
+            duration: Duration::from_secs(60),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn grid_search() {
+        let space = Space {
+            cc: (0.01, 0.5),
+            ce: (0.25, 0.25),
+            gravity_rho: (150.0, 150.0),
+            ..Default::default()
+        };
+        let t = tune::<VecD<2>, _>(
+            &truth(),
+            &scenario(),
+            &space,
+            Search::Grid { steps: 3 },
+            Objective::MedianError,
+            &mut StdRng::seed_from_u64(1),
+        )
+        .unwrap();
+
+        assert_eq!(t.trials.len(), 3);
+        assert!(t.trials.windows(2).all(|w| w[0].score <= w[1].score));
+        assert_eq!(t.best().score, t.best().median_error);
+        // A tiny cc barely moves in a minute
+        assert_eq!(t.trials[2].cfg.cc, 0.01);
+    }
+
+    #[test]
+    fn random_search_reproducible() {
+        let run = |seed| {
+            tune::<VecD<2>, _>(
+                &truth(),
+                &scenario(),
+                &Space::default(),
+                Search::Random { trials: 3 },
+                Objective::Stability,
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap()
+            .trials
+        };
+        let scores = |t: &[Trial]| t.iter().map(|t| t.score).collect::<Vec<_>>();
+        let a = run(5);
+        assert_eq!(scores(&a), scores(&run(5)));
+        assert_ne!(scores(&a), scores(&run(6)));
+        for t in &a {
+            assert_eq!(t.score, t.stability);
+            assert!((0.05..=0.5).contains(&t.cfg.cc));
+            assert_eq!(t.cfg.error_max, Config::default().error_max);
+        }
+    }
+
+    #[test]
+    fn keeps_unsearched_fields() {
+        let node = Config {
+            signed_adjustments: true,
+            legacy_error: true,
+            adjustment_aggregate: window::Aggregate::Median,
+            ..Default::default()
+        };
+        let scenario = SimConfig { node, ..scenario() };
+        for search in [Search::Grid { steps: 2 }, Search::Random { trials: 2 }] {
+            let t = tune::<VecD<2>, _>(
+                &truth(),
+                &scenario,
+                &Space::default(),
+                search,
+                Objective::MedianError,
+                &mut StdRng::seed_from_u64(0),
+            )
+            .unwrap();
+            for trial in &t.trials {
+                assert!(trial.cfg.signed_adjustments && trial.cfg.legacy_error);
+                assert_eq!(trial.cfg.adjustment_aggregate, node.adjustment_aggregate);
+            }
+        }
+    }
+
+    #[test]
+    fn tables() {
+        let t = tune::<VecD<2>, _>(
+            &truth(),
+            &scenario(),
+            &Space::default(),
+            Search::Random { trials: 2 },
+            Objective::P90Error,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap();
+
+        let mut csv = String::new();
+        t.write_csv(&mut csv).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.starts_with("cc,ce,gravity_rho,"));
+
+        let mut md = String::new();
+        t.write_markdown(&mut md).unwrap();
+        assert_eq!(md.lines().count(), 4);
+        assert!(md.lines().all(|l| l.starts_with('|') && l.ends_with('|')));
+    }
+
+    #[test]
+    fn invalid_space() {
+        let bad = [
+            Space {
+                cc: (0.0, 0.5),
+                ..Default::default()
+            },
+            Space {
+                ce: (0.5, 0.1),
+                ..Default::default()
+            },
+            Space {
+                height_min: (-1.0, 0.0),
+                ..Default::default()
+            },
+        ];
+        for space in bad {
+            assert!(tune::<VecD<2>, _>(
+                &truth(),
+                &scenario(),
+                &space,
+                Search::Grid { steps: 2 },
+                Objective::MedianError,
+                &mut StdRng::seed_from_u64(0),
+            )
+            .unwrap_err()
+            .is_invalid_config());
+        }
+        assert!(tune::<VecD<2>, _>(
+            &truth(),
+            &scenario(),
+            &Space::default(),
+            Search::Random { trials: 0 },
+            Objective::MedianError,
+            &mut StdRng::seed_from_u64(0),
+        )
+        .unwrap_err()
+        .is_invalid_config());
+    }
+
+    #[test]
+    fn compare_variants() {
+        let c = compare(
+            &truth(),
+            &scenario(),
+            &[1, 2, 3],
+            0.001,
+            &mut StdRng::seed_from_u64(2),
+        )
+        .unwrap();
+
+        assert_eq!(c.variants.len(), 3 * 4);
+        let keys: Vec<_> = c
+            .variants
+            .iter()
+            .map(|v| (v.dimensions, v.height, v.window))
+            .collect();
+        assert_eq!(
+            keys[..4],
+            [
+                (1, false, false),
+                (1, false, true),
+                (1, true, false),
+                (1, true, true)
+            ]
+        );
+        assert_eq!(keys[11], (3, true, true));
+        // Every variant runs the same probes, so only the options differ
+        let median = |i: usize| c.variants[i].accuracy.relative.median;
+        assert_ne!(median(0), median(1));
+        assert_ne!(median(0), median(2));
+        // Overlapping nodes spread out in random directions, so a second
+        // dimension is actually used
+        assert!(median(4) < median(0) && median(4) < median(1));
+        assert!(c
+            .variants
+            .iter()
+            .all(|v| c.best().accuracy.relative.median <= v.accuracy.relative.median));
+
+        let mut md = String::new();
+        c.write_markdown(&mut md).unwrap();
+        assert_eq!(md.lines().count(), 2 + 12);
+        let mut csv = String::new();
+        c.write_csv(&mut csv).unwrap();
+        assert_eq!(csv.lines().nth(1).unwrap().split(',').count(), 8);
+    }
+
+    #[test]
+    fn compare_invalid() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (dims, height_min) in [
+            (&[][..], 0.001),
+            (&[0][..], 0.001),
+            (&[MAX_DIMENSIONS + 1][..], 0.001),
+            (&[2][..], 0.0),
+        ] {
+            assert!(compare(&truth(), &scenario(), dims, height_min, &mut rng)
+                .unwrap_err()
+                .is_invalid_config());
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! Windows of adjustment samples kept by a [`Node`](crate::Node)
+//!
+//! Each update a node records the residual between the measured RTT and the
+//! estimate from its coordinate. The [`Window`] holds the most recent of these
+//! [`Sample`]s and an [`Aggregate`] of them becomes the node's offset, making
+//! up for what the coordinates can't capture. The window comes in a heapless
+//! ([`heapless::Window`](crate::heapless::Window)) and a heap
+//! ([`heap::Window`](crate::heap::Window), requires the `alloc` feature)
+//! variant, both of a fixed capacity `N`. A capacity of `0` disables
+//! adjustments entirely.
+//!
+//! Samples can also expire after [`Config::adjustment_max_age`], measured by
+//! the time given to [`Node::set_now`].
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use violin::{
+//!     heapless::{VecD, Window},
+//!     window::{Aggregate, Window as _},
+//!     Config, Coord, Node,
+//! };
+//!
+//! let cfg = Config {
+//!     adjustment_aggregate: Aggregate::Median,
+//!     adjustment_max_age: Some(Duration::from_secs(60)),
+//!     ..Default::default()
+//! };
+//! let mut node = Node::<VecD<2>, Window<16>>::with_config(cfg);
+//! let other = Coord::from(VecD::from([0.05, 0.0]));
+//! node.update(Duration::from_millis(60), &other);
+//! assert_eq!(node.adjustments().len(), 1);
+//!
+//! // a minute later the sample has expired
+//! node.set_now(Duration::from_secs(61));
+//! assert!(node.adjustments().is_empty());
+//! assert_eq!(node.coordinate().offset(), 0.0);
+//! ```
+//!
+//! [`Config::adjustment_max_age`]: crate::Config::adjustment_max_age
+//! [`Node::set_now`]: crate::Node::set_now
+
+use crate::std::time::Duration;
+
+/// A single adjustment sample
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Sample {
+    /// The measured RTT minus the estimate, in seconds
+    pub residual: f64,
+    /// When the sample was taken
+    pub at: Duration,
+}
+
+/// How the samples in a [`Window`] are combined
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Aggregate {
+    /// The sum of the samples divided by the window's capacity, so missing
+    /// samples count as zero and adjustments ramp up as the window fills
+    Mean,
+    /// The median of the samples in the window, which ignores the occasional
+    /// slow probe
+    Median,
+    /// The mean of the samples in the window after dropping this fraction
+    /// (`0.0` up to below `0.5`) of the lowest and of the highest
+    TrimmedMean(f64),
+}
+
+impl Default for Aggregate {
+    fn default() -> Self { Self::Mean }
+}
+
+/// A fixed capacity buffer of the most recent adjustment [`Sample`]s
+pub trait Window: Default {
+    /// Returns the maximum number of samples kept
+    fn capacity(&self) -> usize;
+
+    /// Returns the samples in the window, oldest first
+    fn samples(&self) -> &[Sample];
+
+    /// Adds a sample, replacing the oldest if the window is full
+    fn push(&mut self, sample: Sample);
+
+    /// Removes every sample taken before `cutoff`
+    fn expire(&mut self, cutoff: Duration);
+
+    /// Removes every sample
+    fn clear(&mut self);
+
+    /// Returns the number of samples in the window
+    fn len(&self) -> usize { self.samples().len() }
+
+    /// Returns `true` if the window holds no samples
+    fn is_empty(&self) -> bool { self.samples().is_empty() }
+
+    /// Combines the samples, or returns `0.0` if there are none
+    ///
+    /// Order statistics are found by ranking the samples in place, which is
+    /// quadratic in the number of samples but needs no allocation.
+    fn aggregate(&self, how: Aggregate) -> f64 {
+        let samples = self.samples();
+        let len = samples.len();
+        if len == 0 {
+            return 0.0;
+        }
+        match how {
+            Aggregate::Mean => {
+                samples.iter().map(|s| s.residual).sum::<f64>() / self.capacity() as f64
+            }
+            Aggregate::Median => {
+                let mid = nth(samples, len / 2);
+                if len % 2 == 0 {
+                    (nth(samples, len / 2 - 1) + mid) / 2.0
+                } else {
+                    mid
+                }
+            }
+            Aggregate::TrimmedMean(fraction) => {
+                // A NaN fraction casts to 0 and trims nothing
+                let trim = usize::min((len as f64 * fraction) as usize, (len - 1) / 2);
+                let kept = (0..len)
+                    .filter(|i| (trim..len - trim).contains(&rank(samples, *i)))
+                    .map(|i| samples[i].residual);
+                kept.sum::<f64>() / (len - 2 * trim) as f64
+            }
+        }
+    }
+}
+
+/// Returns the position of `samples[i]` if the samples were sorted, breaking
+/// ties by index so every sample has its own rank
+fn rank(samples: &[Sample], i: usize) -> usize {
+    let r = samples[i].residual;
+    samples
+        .iter()
+        .enumerate()
+        .filter(|(j, s)| s.residual < r || (s.residual == r && *j < i))
+        .count()
+}
+
+/// Returns the residual that would be at position `n` if the samples were
+/// sorted
+fn nth(samples: &[Sample], n: usize) -> f64 {
+    (0..samples.len())
+        .find(|i| rank(samples, *i) == n)
+        .map_or(0.0, |i| samples[i].residual)
+}
+
+/// Adds `sample` to the first `len` entries of `buf`, shifting out the oldest
+/// when full
+pub(crate) fn push(buf: &mut [Sample], len: &mut usize, sample: Sample) {
+    if buf.is_empty() {
+        return;
+    }
+    if *len == buf.len() {
+        buf.rotate_left(1);
+        *len -= 1;
+    }
+    buf[*len] = sample;
+    *len += 1;
+}
+
+/// Removes the entries of the first `len` of `buf` taken before `cutoff`
+pub(crate) fn expire(buf: &mut [Sample], len: &mut usize, cutoff: Duration) {
+    let mut kept = 0;
+    for i in 0..*len {
+        if buf[i].at >= cutoff {
+            buf[kept] = buf[i];
+            kept += 1;
+        }
+    }
+    *len = kept;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heapless;
+
+    fn window<const N: usize>(residuals: &[f64]) -> heapless::Window<N> {
+        let mut w = heapless::Window::default();
+        for (i, r) in residuals.iter().enumerate() {
+            w.push(Sample {
+                residual: *r,
+                at: Duration::from_secs(i as u64),
+            });
+        }
+        w
+    }
+
+    #[test]
+    fn ring() {
+        let w = window::<3>(&[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(w.capacity(), 3);
+        let residuals: [f64; 3] = [0, 1, 2].map(|i| w.samples()[i].residual);
+        assert_eq!(residuals, [2.0, 3.0, 4.0]);
+
+        let mut empty = window::<0>(&[1.0]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.aggregate(Aggregate::Median), 0.0);
+        empty.clear();
+    }
+
+    #[test]
+    fn expiry() {
+        let mut w = window::<4>(&[1.0, 2.0, 3.0, 4.0]);
+        w.expire(Duration::from_secs(2));
+        assert_eq!(w.len(), 2);
+        assert_eq!(w.samples()[0].residual, 3.0);
+        // the window fills up again from where it left off
+        w.push(Sample::default());
+        assert_eq!(w.samples()[2], Sample::default());
+        w.clear();
+        assert!(w.is_empty());
+    }
+
+    #[test]
+    fn aggregates() {
+        let w = window::<8>(&[0.5, 0.25, 9.0, 0.125, 0.125, -4.0]);
+        // the mean counts the two missing samples as zero
+        assert_eq!(w.aggregate(Aggregate::Mean), 6.0 / 8.0);
+        assert_eq!(w.aggregate(Aggregate::Median), 0.1875);
+        assert_eq!(w.aggregate(Aggregate::TrimmedMean(0.2)), 1.0 / 4.0);
+        assert_eq!(w.aggregate(Aggregate::TrimmedMean(0.0)), 1.0);
+        // trimming never drops every sample
+        assert_eq!(w.aggregate(Aggregate::TrimmedMean(0.9)), 0.1875);
+        assert_eq!(w.aggregate(Aggregate::TrimmedMean(f64::NAN)), 1.0);
+
+        let odd = window::<3>(&[3.0, -1.0, 2.0]);
+        assert_eq!(odd.aggregate(Aggregate::Median), 2.0);
+    }
+}